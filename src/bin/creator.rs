@@ -1,6 +1,7 @@
 #![allow(unused_imports)]
 
 use nids2::game::*;
+use nids2::object::AnimationState;
 use nids2::util::*;
 use raylib::consts::KeyboardKey::*;
 use raylib::prelude::*;
@@ -164,6 +165,8 @@ fn main() {
         .size(scr_w, scr_h)
         .resizable()
         .build();
+    // The layouts squeeze below this, but nothing fits any more
+    handle.set_window_min_size(640, 480);
 
     handle.set_target_fps(60);
     handle.gui_load_style(Some(rstr!("candy.rgs")));
@@ -173,6 +176,8 @@ fn main() {
 
     let mut object_mode = false;
     let mut bounding_box_mode = false;
+    let mut animation_mode = false;
+    let mut metadata_mode = false;
     let mut obj_preview_mode = false;
     let mut animating = false;
 
@@ -197,6 +202,21 @@ fn main() {
     let mut edit_object = 0;
     let mut top_item_index = 0;
     let mut preview_subimage = 0;
+    let mut bbox_side = 0;
+    let mut bbox_scope = 0;
+    let mut anim_clip = 0;
+    let mut anim_clip_top = 0;
+    let mut anim_clip_frame = 0;
+    let mut anim_preview: Option<AnimationState> = None;
+    let mut meta_display_name = String::new();
+    let mut meta_description = String::new();
+    let mut meta_tags = String::new();
+    let mut name_input = TextInput::new(); // Text boxes, which remember their cursor between frames
+    let mut category_input = TextInput::new();
+    let mut clip_name_input = TextInput::new();
+    let mut display_name_input = TextInput::new();
+    let mut description_input = TextInput::new();
+    let mut tags_input = TextInput::new();
 
     let mut err: Option<(String, i32)> = None;
 
@@ -250,8 +270,8 @@ fn main() {
             anim_speed = 0;
         }
 
-        let k = handle.get_key_pressed();
         let mut d = handle.begin_drawing(&thread);
+        focus_begin_frame(&d);
         // let mut d = d.begin_texture_mode(&thread, &mut target);
 
         d.clear_background(Color::SKYBLUE);
@@ -264,26 +284,36 @@ fn main() {
          * same mechanisms as the game does.
          * */
         if !object_mode {
-            let menu_rect = rrect(16, 16, scr_w - 32, scr_h - 32);
+            let menu = Layout::column(rrect(0, 0, scr_w, scr_h))
+                .padding(16.0)
+                .spacing(16.0)
+                .split(&[Size::Fixed(96.0), Size::Fill(1.0)]);
             // Draw Explanation of Screen
+            let lines = Layout::column(menu[0]).split_even(2);
+            let (cx, cy) = rect_midpoint(lines[0]);
             draw_text_centered(
                 &mut d,
                 &font,
                 "Drag and Drop a PNG file onto the window to create a new object!",
-                scr_w / 2,
-                64,
+                cx,
+                cy,
                 24,
                 Color::BLACK,
             );
+            let (cx, cy) = rect_midpoint(lines[1]);
             draw_text_centered(
                 &mut d,
                 &font,
                 "Or, Select An Existing Object to Edit Using Your Mouse!",
-                scr_w / 2,
-                96,
+                cx,
+                cy,
                 24,
                 Color::BLACK,
             );
+            // Object list on the left, preview of the picked object on the right
+            let body = Layout::row(menu[1])
+                .spacing(16.0)
+                .split(&[Size::Fixed(240.0), Size::Fill(1.0)]);
 
             // Get all existing objects by collecting the read_dir iterator
             let mut items = std::fs::read_dir("obj/")
@@ -305,12 +335,7 @@ fn main() {
             if ds_scroll_selection(
                 &mut d,
                 &font,
-                rrect(
-                    menu_rect.x + 16.0,
-                    128.0,
-                    240,
-                    (menu_rect.y + menu_rect.height) - 128.0,
-                ),
+                body[0],
                 &items,
                 &mut edit_object,
                 &mut top_item_index,
@@ -340,9 +365,19 @@ fn main() {
                         image_rect.height,
                     );
 
-                    let frame_rect = rrect(menu_rect.x + 260.0, 128, 320, 320);
-                    scale_to(&mut image_rect, 320.0, 320.0);
+                    // Square frame, then the subimage buttons, the load button and the animation toggle
+                    let parts = Layout::column(body[1]).spacing(8.0).split(&[
+                        Size::Fill(1.0),
+                        Size::Fixed(40.0),
+                        Size::Fixed(32.0),
+                        Size::Fixed(32.0),
+                    ]);
+                    let side_len = parts[0].width.min(parts[0].height);
+                    let frame_rect = anchor_rect(parts[0], side_len, side_len, Anchor::Top);
+                    scale_to(&mut image_rect, frame_rect.width, frame_rect.height);
                     center_in(&mut image_rect, frame_rect);
+                    let nav = Layout::row(anchor_rect(parts[1], side_len, 40.0, Anchor::Centre))
+                        .split(&[Size::Fixed(56.0), Size::Fill(1.0), Size::Fixed(56.0)]);
 
                     // Frame
                     ds_rounded_rectangle_lines(&mut d, frame_rect, 0.05, 16, 3);
@@ -357,35 +392,25 @@ fn main() {
                     );
 
                     // Warning Messsage
+                    let warning = Layout::column(nav[1]).split_even(2);
+                    let (cx, cy) = rect_midpoint(warning[0]);
+                    draw_text_centered(&mut d, &font, "NOT TO REAL SIZE", cx, cy, 16, Color::BLACK);
+                    let (cx, cy) = rect_midpoint(warning[1]);
                     draw_text_centered(
                         &mut d,
                         &font,
                         "Image Is Scaled To Fit In The Frame",
-                        frame_rect.x as i32 + 160,
-                        (frame_rect.y + frame_rect.height) as i32 + 32,
-                        16,
-                        Color::BLACK,
-                    );
-                    draw_text_centered(
-                        &mut d,
-                        &font,
-                        "NOT TO REAL SIZE",
-                        frame_rect.x as i32 + 160,
-                        (frame_rect.y + frame_rect.height) as i32 + 12,
+                        cx,
+                        cy,
                         16,
                         Color::BLACK,
                     );
 
                     // Previous Subimage and Next Subimage buttons
-                    if ds_rounded_button_centered(
+                    if ds_rounded_button(
                         &mut d,
                         &font,
-                        rrect(
-                            frame_rect.x + (1.0 / 8.0) * frame_rect.width,
-                            frame_rect.y + frame_rect.height + 12.0,
-                            48,
-                            16,
-                        ),
+                        anchor_rect(nav[0], 56.0, 24.0, Anchor::Centre),
                         Some("prev"),
                         preview_obj.1.img_per_side > 1 && !animating,
                     )
@@ -397,15 +422,10 @@ fn main() {
                         }
                     }
 
-                    if ds_rounded_button_centered(
+                    if ds_rounded_button(
                         &mut d,
                         &font,
-                        rrect(
-                            frame_rect.x + (7.0 / 8.0) * frame_rect.width,
-                            frame_rect.y + frame_rect.height + 12.0,
-                            48,
-                            16,
-                        ),
+                        anchor_rect(nav[2], 56.0, 24.0, Anchor::Centre),
                         Some("next"),
                         preview_obj.1.img_per_side > 1 && !animating,
                     )
@@ -429,16 +449,11 @@ fn main() {
                         //     Some(rstr!("toggle animation")),
                         //     animating,
                         // );
-                        ds_draw_toggle_rounded_centered(
+                        ds_draw_toggle_rounded(
                             &mut d,
                             &font,
                             Some("toggle animation"),
-                            rrect(
-                                frame_rect.x + frame_rect.width / 2.,
-                                frame_rect.y + frame_rect.height + 88.,
-                                184,
-                                24,
-                            ),
+                            anchor_rect(parts[3], 184.0, 24.0, Anchor::Centre),
                             &mut animating,
                         );
 
@@ -451,15 +466,10 @@ fn main() {
                     }
 
                     // Object Select button
-                    if ds_rounded_button_centered(
+                    if ds_rounded_button(
                         &mut d,
                         &font,
-                        rrect(
-                            frame_rect.x + frame_rect.width / 2.0,
-                            frame_rect.y + frame_rect.height + 64.0,
-                            184,
-                            24,
-                        ),
+                        anchor_rect(parts[2], 184.0, 24.0, Anchor::Centre),
                         Some("LOAD AND EDIT OBJECT"),
                         true,
                     )
@@ -494,10 +504,31 @@ fn main() {
             }
         /* DRAW GUI AND INTERFACE */
         } else {
+            // Sprite sheet and preview on the left, controls on the right
+            let screen = rrect(0, 0, scr_w, scr_h);
+            let halves = Layout::row(screen).split_even(2);
+            let left = Layout::column(halves[0]).split(&[
+                Size::Fill(1.0),
+                Size::Fill(1.0),
+                Size::Fixed(32.0),
+            ]);
+            let panel = Layout::column(halves[1]).split(&[
+                Size::Fixed(64.0), // name
+                Size::Fixed(64.0), // category
+                Size::Fixed(64.0), // sides
+                Size::Fixed(64.0), // subimages
+                Size::Fixed(64.0), // animation speed
+                Size::Fixed(64.0), // editor buttons
+                Size::Fixed(64.0), // save and exit
+                Size::Fill(1.0),   // error messages
+            ]);
+
             // draw texture appropriately to fit on screen.
             let mut sprsht_rec = rrect(0, 0, spritesheet.width(), spritesheet.height());
             let src_rect = sprsht_rec;
-            scale_to(&mut sprsht_rec, (scr_w / 2) as f32, (scr_h / 2) as f32);
+            scale_to(&mut sprsht_rec, left[0].width, left[0].height);
+            sprsht_rec.x = left[0].x;
+            sprsht_rec.y = left[0].y;
             d.draw_texture_pro(
                 &spritesheet,
                 src_rect,
@@ -514,52 +545,45 @@ fn main() {
                     spritesheet.height()
                 )
                 .as_str(),
-                rvec2(0, scr_h - 32),
+                rvec2(left[2].x, left[2].y),
                 24.0,
                 1.0,
                 Color::BLACK,
             );
 
             // DRAW IMPORTANT CONTROLS
-            d.gui_panel(Rectangle {
-                x: (scr_w / 2) as f32,
-                y: 0 as f32,
-                width: (scr_w / 2) as f32,
-                height: scr_h as f32,
-            });
-            advanced_input(
+            d.gui_panel(halves[1]);
+            name_input.draw(
                 &mut d,
-                &k,
                 &font,
-                rrect(scr_w / 2, 0, scr_w / 2, 64),
+                panel[0],
                 &mut obj.conf.name,
                 &"Enter The Object Name".to_string(),
             );
-            advanced_input(
+            category_input.draw(
                 &mut d,
-                &k,
                 &font,
-                rrect(scr_w / 2, 64, scr_w / 2, 64),
+                panel[1],
                 &mut obj.conf.category,
                 &"Enter The Object Category".to_string(),
             );
             side_selector(
                 &mut d,
-                rrect(scr_w / 2, 128, scr_w / 2, 64),
+                panel[2],
                 &side_options_str,
                 &CString::new("Select number of sides").unwrap(),
                 &mut side,
             );
             side_selector(
                 &mut d,
-                rrect(scr_w / 2, 194, scr_w / 2, 64),
+                panel[3],
                 &subimage_options_str,
                 &CString::new("Select number of subimages").unwrap(),
                 &mut subimage,
             );
             anim_selector(
                 &mut d,
-                rrect(scr_w / 2, 258, scr_w / 2, 64),
+                panel[4],
                 &CString::new("Select animation speed").unwrap(),
                 0,
                 64,
@@ -578,9 +602,8 @@ fn main() {
                 }
                 let spr_rect = rrect(spr_w * cur_subimg, spr_h * side, spr_w, spr_h);
                 let mut draw_rect = spr_rect;
-                scale_to(&mut draw_rect, (scr_w / 2) as f32, 290.0);
-                draw_rect.x = (scr_w as f32 * 0.75) - (draw_rect.width / 2.0);
-                draw_rect.y = scr_h as f32 - draw_rect.height;
+                scale_to(&mut draw_rect, left[1].width, left[1].height);
+                center_in(&mut draw_rect, left[1]);
                 d.draw_texture_pro(
                     &spritesheet,
                     spr_rect,
@@ -592,10 +615,13 @@ fn main() {
                 // anim_frame(&mut d, &spritesheet, spr_w, spr_h, side, cur_subimg, pos);
             }
 
+            // Only one editor may be drawn over the screen at a time
+            let editor_open = bounding_box_mode || animation_mode || metadata_mode;
+
             /* DRAW AND EXECUTE SAVE AND EXIT */
-            if !bounding_box_mode
+            if !editor_open
                 && d.gui_button(
-                    rrect(scr_w / 2, 386, scr_w / 2, 64),
+                    panel[6],
                     Some(CString::new("Save and Exit").unwrap().as_c_str()),
                 )
             {
@@ -640,16 +666,46 @@ fn main() {
 
                 let color = Color::DARKPURPLE.fade(fade);
                 if frame_count < max_frame {
-                    draw_text_centered(&mut d, &font, e, scr_w / 4 * 3, 450, 24, color);
+                    let (cx, cy) = rect_midpoint(panel[7]);
+                    draw_text_centered(&mut d, &font, e, cx, cy, 24, color);
                 } else {
                     err = None;
                 }
             }
 
+            let editor_buttons = Layout::row(panel[5]).split_even(3);
+
+            /* METADATA EDITOR BUTTON */
+            if !editor_open
+                && d.gui_button(
+                    editor_buttons[2],
+                    Some(CString::new("Edit Metadata").unwrap().as_c_str()),
+                )
+            {
+                metadata_mode = true;
+                meta_display_name = obj.conf.display_name.clone().unwrap_or_default();
+                meta_description = obj.conf.description.clone().unwrap_or_default();
+                meta_tags = obj.conf.tags.join(",");
+            }
+
+            /* ANIMATION EDITOR BUTTON */
+            if !editor_open
+                && d.gui_button(
+                    editor_buttons[1],
+                    Some(CString::new("Edit Animations").unwrap().as_c_str()),
+                )
+            {
+                animation_mode = true;
+                anim_clip = 0;
+                anim_clip_top = 0;
+                anim_clip_frame = 0;
+                anim_preview = None;
+            }
+
             /* BOUNDING BOX BUTTON */
-            if !bounding_box_mode
+            if !editor_open
                 && d.gui_button(
-                    rrect(scr_w / 2, 322, scr_w / 2, 64),
+                    editor_buttons[0],
                     Some(CString::new("Create Bounding Box").unwrap().as_c_str()),
                 )
             {
@@ -667,49 +723,68 @@ fn main() {
 
             /* DRAW BOUNDING BOX EDITOR OVER REST OF SCREEN */
             if bounding_box_mode {
-                let mode_rect = rrect(
-                    scr_w as f32 * 0.25,
-                    scr_h as f32 * 0.25,
-                    scr_w / 2,
-                    scr_h / 2,
-                );
+                let mode_rect = inset(screen, 48.0);
                 ds_rounded_rectangle(&mut d, mode_rect, 0.5, 4);
-                let (x, y, width, height): (&mut i32, &mut i32, &mut i32, &mut i32) = obj
-                    .conf
-                    .default_b_box
-                    .as_mut()
-                    .map(|tup| (&mut tup.0, &mut tup.1, &mut tup.2, &mut tup.3))
-                    .unwrap();
-
-                let src_rect = rrect(preview_subimage * obj.conf.dim.0, 0, obj.conf.dim.0, obj.conf.dim.1);
-                let mut spr_rect = rrect(
-                    mode_rect.x + mode_rect.width / 2.0,
-                    mode_rect.y + mode_rect.height / 2.0,
-                    src_rect.width,
-                    src_rect.height,
-                );
-
-                let target_w = mode_rect.width / 2.0;
-                let target_h = mode_rect.height / 2.0;
-
-                if spr_rect.width < (target_w - 60.0) {
-                    spr_rect.height *= target_w / spr_rect.width;
-                    spr_rect.width = target_w;
-                } else if spr_rect.height < (target_h - 60.0) {
-                    spr_rect.width *= target_h / spr_rect.height;
-                    spr_rect.height = target_h;
-                }
-
-                if spr_rect.width > (target_w) {
-                    spr_rect.height *= target_w / spr_rect.width;
-                    spr_rect.width = target_w;
-                } else if spr_rect.height > (target_h) {
-                    spr_rect.width *= target_h / spr_rect.height;
-                    spr_rect.height = target_h;
+                // Scope button and box size on top, sliders either side of the sprite, buttons below
+                let parts = Layout::column(mode_rect).padding(8.0).spacing(4.0).split(&[
+                    Size::Fixed(24.0),
+                    Size::Fixed(32.0),
+                    Size::Fill(1.0),
+                    Size::Fixed(32.0),
+                ]);
+                let body = Layout::row(parts[2]).spacing(8.0).split(&[
+                    Size::Fill(1.0),
+                    Size::Fill(2.0),
+                    Size::Fill(1.0),
+                ]);
+                let sliders_left = Layout::column(body[0]).split_even(2);
+                let sliders_right = Layout::column(body[2]).split_even(2);
+                let sprite_area = Layout::column(body[1])
+                    .spacing(4.0)
+                    .split(&[Size::Fill(1.0), Size::Fixed(24.0)]);
+                let sprite_nav = Layout::row(sprite_area[1]).split(&[
+                    Size::Fixed(56.0),
+                    Size::Fill(1.0),
+                    Size::Fixed(56.0),
+                ]);
+                let footer = Layout::row(parts[3]).spacing(8.0).split_even(3);
+                // Pick the box being edited: the default box (scope 0), the box of the current side
+                // (scope 1) or the box of the current subimage of that side (scope 2)
+                let scope_subimage = if bbox_scope == 2 {
+                    Some(preview_subimage)
+                } else {
+                    None
+                };
+                let editable = bbox_scope == 0
+                    || obj
+                        .conf
+                        .get_b_box_override(bbox_side, scope_subimage)
+                        .is_some();
+                let mut edit_box = match bbox_scope {
+                    0 => obj.conf.default_b_box,
+                    1 => obj
+                        .conf
+                        .get_b_box_override(bbox_side, None)
+                        .or(obj.conf.default_b_box),
+                    _ => obj.conf.b_box_for(bbox_side, preview_subimage),
                 }
+                .unwrap_or((0, 0, 1, 1));
+                let (x, y, width, height) = (
+                    &mut edit_box.0,
+                    &mut edit_box.1,
+                    &mut edit_box.2,
+                    &mut edit_box.3,
+                );
 
-                spr_rect.x -= spr_rect.width / 2.0;
-                spr_rect.y -= spr_rect.height / 2.0;
+                let src_rect = rrect(
+                    preview_subimage * obj.conf.dim.0,
+                    bbox_side * obj.conf.dim.1,
+                    obj.conf.dim.0,
+                    obj.conf.dim.1,
+                );
+                let mut spr_rect = src_rect;
+                scale_to(&mut spr_rect, sprite_area[0].width, sprite_area[0].height);
+                center_in(&mut spr_rect, sprite_area[0]);
 
                 d.draw_texture_pro(
                     &spritesheet,
@@ -720,125 +795,150 @@ fn main() {
                     Color::WHITE,
                 );
 
-                ds_draw_slider_centered(
+                let scope_name = match bbox_scope {
+                    0 => String::from("editing: default box"),
+                    1 => format!("editing: side {}", bbox_side),
+                    _ => format!("editing: side {} subimage {}", bbox_side, preview_subimage),
+                };
+                if ds_rounded_button(
                     &mut d,
                     &font,
-                    "Modify BBox X",
-                    rvec2(
-                        mode_rect.x + mode_rect.width * (1.5 / 8.0),
-                        mode_rect.y + mode_rect.height * (1.0 / 3.0),
-                    ),
-                    mode_rect.width / 4.0,
-                    20.0,
-                    x,
-                    0.0,
-                    src_rect.width - *width as f32,
-                    true,
-                );
-                ds_draw_slider_centered(
-                    &mut d,
-                    &font,
-                    "Modify BBox Y",
-                    rvec2(
-                        mode_rect.x + mode_rect.width * (1.5 / 8.0),
-                        mode_rect.y + mode_rect.height * (2.0 / 3.0),
-                    ),
-                    mode_rect.width / 4.0,
-                    20.0,
-                    y,
-                    0.0,
-                    src_rect.height - *height as f32,
-                    true,
-                );
-                ds_draw_slider_centered(
-                    &mut d,
-                    &font,
-                    "Modify BBOx WIDTH",
-                    rvec2(
-                        mode_rect.x + mode_rect.width * (6.5 / 8.0),
-                        mode_rect.y + mode_rect.height * (1.0 / 3.0),
-                    ),
-                    mode_rect.width / 4.0,
-                    20.0,
-                    width,
-                    0.0,
-                    src_rect.width - *x as f32 + 1.0,
-                    true,
-                );
-                ds_draw_slider_centered(
-                    &mut d,
-                    &font,
-                    "Modify BBOx HEIGHT",
-                    rvec2(
-                        mode_rect.x + mode_rect.width * (6.5 / 8.0),
-                        mode_rect.y + mode_rect.height * (2.0 / 3.0),
-                    ),
-                    mode_rect.width / 4.0,
-                    20.0,
-                    height,
-                    0.0,
-                    src_rect.height - *y as f32 + 1.0,
+                    anchor_rect(parts[0], 220.0, 24.0, Anchor::Centre),
+                    Some(scope_name.as_str()),
                     true,
-                );
+                )
+                .0
+                {
+                    bbox_scope = (bbox_scope + 1) % 3;
+                }
+
+                if !editable {
+                    let (cx, cy) = rect_midpoint(body[0]);
+                    draw_text_centered(
+                        &mut d,
+                        &font,
+                        "Using a less specific box",
+                        cx,
+                        cy,
+                        16,
+                        Color::BLACK,
+                    );
+                } else {
+                    let slider_at = |r: Rectangle| {
+                        let (cx, cy) = rect_midpoint(r);
+                        rvec2(cx, cy)
+                    };
+                    ds_draw_slider_centered(
+                        &mut d,
+                        &font,
+                        "Modify BBox X",
+                        slider_at(sliders_left[0]),
+                        body[0].width,
+                        20.0,
+                        x,
+                        0.0,
+                        src_rect.width - *width as f32,
+                        true,
+                    );
+                    ds_draw_slider_centered(
+                        &mut d,
+                        &font,
+                        "Modify BBox Y",
+                        slider_at(sliders_left[1]),
+                        body[0].width,
+                        20.0,
+                        y,
+                        0.0,
+                        src_rect.height - *height as f32,
+                        true,
+                    );
+                    ds_draw_slider_centered(
+                        &mut d,
+                        &font,
+                        "Modify BBOx WIDTH",
+                        slider_at(sliders_right[0]),
+                        body[0].width,
+                        20.0,
+                        width,
+                        0.0,
+                        src_rect.width - *x as f32 + 1.0,
+                        true,
+                    );
+                    ds_draw_slider_centered(
+                        &mut d,
+                        &font,
+                        "Modify BBOx HEIGHT",
+                        slider_at(sliders_right[1]),
+                        body[0].width,
+                        20.0,
+                        height,
+                        0.0,
+                        src_rect.height - *y as f32 + 1.0,
+                        true,
+                    );
+                }
 
+                let (cx, cy) = rect_midpoint(parts[1]);
                 draw_text_centered(
                     &mut d,
                     &font,
                     format!("({}, {}) {}px X {}px", *x, *y, *width, *height).as_str(),
-                    (mode_rect.x + mode_rect.width / 2.0) as i32,
-                    (mode_rect.y + 45.0) as i32,
+                    cx,
+                    cy,
                     24,
                     Color::BLACK,
                 );
 
-                let (exit_bbox, _) = ds_rounded_button_centered(
+                let (exit_bbox, _) = ds_rounded_button(
                     &mut d,
                     &font,
-                    rrect(
-                        mode_rect.x + mode_rect.width / 2.0,
-                        mode_rect.y + mode_rect.height * 0.875,
-                        120,
-                        30,
-                    ),
+                    anchor_rect(footer[1], 120.0, 30.0, Anchor::Centre),
                     Some("Exit BBox Editor"),
                     true,
                 );
 
-                // Previous Subimage and Next Subimage buttons
-                if ds_rounded_button_centered(
+                // Create or drop the override for the current scope
+                let mut toggle_override = false;
+                if bbox_scope != 0 {
+                    toggle_override = ds_rounded_button(
+                        &mut d,
+                        &font,
+                        anchor_rect(footer[0], 120.0, 30.0, Anchor::Centre),
+                        Some(if editable {
+                            "Remove Override"
+                        } else {
+                            "Add Override"
+                        }),
+                        true,
+                    )
+                    .0;
+                }
+
+                // Previous Side and Next Side buttons
+                if ds_rounded_button(
                     &mut d,
                     &font,
-                    rrect(
-                        spr_rect.x + (1.0 / 8.0) * spr_rect.width,
-                        spr_rect.y + spr_rect.height + 12.0,
-                        48,
-                        16,
-                    ),
-                    Some("prev"),
-                    true,
+                    anchor_rect(footer[2], 56.0, 24.0, Anchor::Centre),
+                    Some("side"),
+                    obj.conf.sides > 1,
                 )
                 .0
                 {
+                    bbox_side += 1;
+                    if bbox_side >= obj.conf.sides {
+                        bbox_side = 0;
+                    }
+                }
+
+                // Previous Subimage and Next Subimage buttons
+                if ds_rounded_button(&mut d, &font, sprite_nav[0], Some("prev"), true).0 {
                     preview_subimage -= 1;
                     if preview_subimage < 0 {
                         preview_subimage = obj.conf.img_per_side - 1;
                     }
                 }
 
-                if ds_rounded_button_centered(
-                    &mut d,
-                    &font,
-                    rrect(
-                        spr_rect.x + (7.0 / 8.0) * spr_rect.width,
-                        spr_rect.y + spr_rect.height + 12.0,
-                        48,
-                        16,
-                    ),
-                    Some("next"),
-                    true,
-                )
-                .0
-                {
+                if ds_rounded_button(&mut d, &font, sprite_nav[2], Some("next"), true).0 {
                     preview_subimage += 1;
                     if preview_subimage >= obj.conf.img_per_side {
                         preview_subimage = 0;
@@ -855,11 +955,399 @@ fn main() {
                 bbox.height *= factor;
                 d.draw_rectangle_lines_ex(bbox, 1, Color::BLACK);
                 d.draw_rectangle_lines_ex(spr_rect, 1, Color::BLACK);
+
+                // Store the edited box back where it came from
+                if toggle_override && editable {
+                    obj.conf.remove_b_box_override(bbox_side, scope_subimage);
+                } else if toggle_override || (editable && bbox_scope != 0) {
+                    obj.conf
+                        .set_b_box_override(bbox_side, scope_subimage, edit_box);
+                } else if editable {
+                    obj.conf.default_b_box = Some(edit_box);
+                }
                 if exit_bbox {
                     bounding_box_mode = false;
                 }
             }
+
+            /* DRAW ANIMATION CLIP EDITOR OVER REST OF SCREEN */
+            if animation_mode {
+                let mode_rect = inset(screen, 32.0);
+                ds_rounded_rectangle(&mut d, mode_rect, 0.1, 4);
+                // Clip list on the left, the selected clip on the right, exit button below
+                let parts = Layout::column(mode_rect)
+                    .padding(8.0)
+                    .spacing(8.0)
+                    .split(&[Size::Fill(1.0), Size::Fixed(30.0)]);
+                let body = Layout::row(parts[0])
+                    .spacing(8.0)
+                    .split(&[Size::Fill(3.0), Size::Fill(7.0)]);
+                let list_area = Layout::column(body[0])
+                    .spacing(4.0)
+                    .split(&[Size::Fill(1.0), Size::Fixed(32.0)]);
+                let list_buttons = Layout::row(list_area[1]).split_even(2);
+                let edit = Layout::column(body[1]).spacing(8.0).split(&[
+                    Size::Fixed(64.0), // clip name
+                    Size::Fixed(16.0), // frame list
+                    Size::Fixed(24.0), // frame buttons
+                    Size::Fixed(24.0), // subimage and mode buttons
+                    Size::Fixed(48.0), // frame duration
+                    Size::Fill(1.0),   // preview
+                ]);
+
+                let spr_count = *subimage_options.get(subimage as usize).unwrap();
+                let spr_w = spritesheet.width() / spr_count;
+                let spr_h = spritesheet.height() / side_options.get(side as usize).unwrap();
+
+                // Clip list
+                let clip_names: Vec<String> =
+                    obj.conf.animations.iter().map(|a| a.name.clone()).collect();
+                if ds_scroll_selection(
+                    &mut d,
+                    &font,
+                    list_area[0],
+                    &clip_names,
+                    &mut anim_clip,
+                    &mut anim_clip_top,
+                ) {
+                    anim_clip_frame = 0;
+                    anim_preview = None;
+                }
+
+                if ds_rounded_button(&mut d, &font, list_buttons[0], Some("add clip"), true).0 {
+                    let name = format!("clip{}", obj.conf.animations.len());
+                    obj.conf.animations.push(AnimationConfig::new(&name));
+                    anim_clip = obj.conf.animations.len() as i32 - 1;
+                    anim_clip_frame = 0;
+                    anim_preview = None;
+                }
+                if ds_rounded_button(
+                    &mut d,
+                    &font,
+                    list_buttons[1],
+                    Some("remove clip"),
+                    !obj.conf.animations.is_empty(),
+                )
+                .0 && (anim_clip as usize) < obj.conf.animations.len()
+                {
+                    obj.conf.animations.remove(anim_clip as usize);
+                    anim_clip = 0;
+                    anim_clip_frame = 0;
+                    anim_preview = None;
+                }
+
+                if let Some(clip) = obj.conf.animations.get_mut(anim_clip as usize) {
+                    clip_name_input.draw(&mut d, &font, edit[0], &mut clip.name, "Clip Name");
+
+                    // Frame list, with the selected frame in brackets
+                    let mut frames_str = String::from("Frames:");
+                    for (i, f) in clip.frames.iter().enumerate() {
+                        if i as i32 == anim_clip_frame {
+                            frames_str
+                                .push_str(format!(" [{}:{}]", f, clip.duration_of(i)).as_str());
+                        } else {
+                            frames_str.push_str(format!(" {}:{}", f, clip.duration_of(i)).as_str());
+                        }
+                    }
+                    d.draw_text_ex(
+                        &font,
+                        frames_str.as_str(),
+                        rvec2(edit[1].x, edit[1].y),
+                        16.0,
+                        1.0,
+                        Color::BLACK,
+                    );
+
+                    let frame_buttons = Layout::row(edit[2]).split_even(4);
+                    if ds_rounded_button(
+                        &mut d,
+                        &font,
+                        frame_buttons[0],
+                        Some("< frame"),
+                        anim_clip_frame > 0,
+                    )
+                    .0
+                    {
+                        anim_clip_frame -= 1;
+                    }
+                    if ds_rounded_button(
+                        &mut d,
+                        &font,
+                        frame_buttons[1],
+                        Some("frame >"),
+                        anim_clip_frame + 1 < clip.frames.len() as i32,
+                    )
+                    .0
+                    {
+                        anim_clip_frame += 1;
+                    }
+                    if ds_rounded_button(&mut d, &font, frame_buttons[2], Some("add frame"), true).0
+                    {
+                        let duration = clip.duration_of(clip.frames.len());
+                        clip.durations.resize(clip.frames.len(), duration);
+                        clip.frames.push(preview_subimage);
+                        clip.durations.push(duration);
+                        anim_clip_frame = clip.frames.len() as i32 - 1;
+                        anim_preview = None;
+                    }
+                    if ds_rounded_button(
+                        &mut d,
+                        &font,
+                        frame_buttons[3],
+                        Some("remove frame"),
+                        !clip.frames.is_empty(),
+                    )
+                    .0 && (anim_clip_frame as usize) < clip.frames.len()
+                    {
+                        clip.frames.remove(anim_clip_frame as usize);
+                        if (anim_clip_frame as usize) < clip.durations.len() {
+                            clip.durations.remove(anim_clip_frame as usize);
+                        }
+                        if anim_clip_frame >= clip.frames.len() as i32 {
+                            anim_clip_frame = max(clip.frames.len() as i32 - 1, 0);
+                        }
+                        anim_preview = None;
+                    }
+
+                    // Subimage that "add frame" will use
+                    let subimage_buttons = Layout::row(edit[3]).split_even(4);
+                    if ds_rounded_button(
+                        &mut d,
+                        &font,
+                        subimage_buttons[0],
+                        Some("prev"),
+                        spr_count > 1,
+                    )
+                    .0
+                    {
+                        preview_subimage -= 1;
+                        if preview_subimage < 0 {
+                            preview_subimage = spr_count - 1;
+                        }
+                    }
+                    let (cx, cy) = rect_midpoint(subimage_buttons[1]);
+                    draw_text_centered(
+                        &mut d,
+                        &font,
+                        format!("subimage {}", preview_subimage).as_str(),
+                        cx,
+                        cy,
+                        16,
+                        Color::BLACK,
+                    );
+                    if ds_rounded_button(
+                        &mut d,
+                        &font,
+                        subimage_buttons[2],
+                        Some("next"),
+                        spr_count > 1,
+                    )
+                    .0
+                    {
+                        preview_subimage += 1;
+                        if preview_subimage >= spr_count {
+                            preview_subimage = 0;
+                        }
+                    }
+
+                    // Playback mode
+                    let mode_name = match clip.mode {
+                        AnimationMode::Loop => "mode: loop",
+                        AnimationMode::PingPong => "mode: ping-pong",
+                        AnimationMode::OneShot => "mode: one-shot",
+                    };
+                    if ds_rounded_button(&mut d, &font, subimage_buttons[3], Some(mode_name), true)
+                        .0
+                    {
+                        clip.mode = match clip.mode {
+                            AnimationMode::Loop => AnimationMode::PingPong,
+                            AnimationMode::PingPong => AnimationMode::OneShot,
+                            AnimationMode::OneShot => AnimationMode::Loop,
+                        };
+                        anim_preview = None;
+                    }
+
+                    // Duration of the selected frame
+                    if (anim_clip_frame as usize) < clip.frames.len() {
+                        let duration = clip.duration_of(clip.frames.len());
+                        clip.durations.resize(clip.frames.len(), duration);
+                        ds_draw_slider_centered(
+                            &mut d,
+                            &font,
+                            "Frame Duration",
+                            rvec2(rect_midpoint(edit[4]).0, edit[4].y + 16.0),
+                            edit[4].width / 2.0,
+                            20.0,
+                            &mut clip.durations[anim_clip_frame as usize],
+                            1.0,
+                            60.0,
+                            true,
+                        );
+                    }
+
+                    // Clip preview
+                    let preview =
+                        anim_preview.get_or_insert_with(|| AnimationState::new(&clip.name));
+                    preview.step(clip);
+                    if let Some(frame) = preview.current_frame(clip) {
+                        let src_rect = rrect(spr_w * frame, 0, spr_w, spr_h);
+                        let mut draw_rect = src_rect;
+                        scale_to(&mut draw_rect, edit[5].width, edit[5].height);
+                        center_in(&mut draw_rect, edit[5]);
+                        d.draw_texture_pro(
+                            &spritesheet,
+                            src_rect,
+                            draw_rect,
+                            rvec2(0, 0),
+                            0.0,
+                            Color::WHITE,
+                        );
+                    }
+                    // Replay one-shot clips so they can be watched more than once
+                    if preview.finished {
+                        anim_preview = None;
+                    }
+                }
+
+                let (exit_anim, _) = ds_rounded_button(
+                    &mut d,
+                    &font,
+                    anchor_rect(parts[1], 160.0, 30.0, Anchor::Centre),
+                    Some("Exit Animation Editor"),
+                    true,
+                );
+                if exit_anim {
+                    animation_mode = false;
+                }
+            }
+
+            /* DRAW METADATA EDITOR OVER REST OF SCREEN */
+            if metadata_mode {
+                let mode_rect = inset(screen, 24.0);
+                ds_rounded_rectangle(&mut d, mode_rect, 0.1, 4);
+
+                let parts = Layout::column(mode_rect).padding(8.0).split(&[
+                    Size::Fixed(64.0), // display name
+                    Size::Fixed(64.0), // description
+                    Size::Fixed(64.0), // tags
+                    Size::Fixed(56.0), // price
+                    Size::Fixed(36.0), // placement flags
+                    Size::Fixed(36.0), // more placement flags and layer
+                    Size::Fill(1.0),
+                    Size::Fixed(30.0), // exit button
+                ]);
+                display_name_input.draw(
+                    &mut d,
+                    &font,
+                    parts[0],
+                    &mut meta_display_name,
+                    "Display Name",
+                );
+                description_input.draw(
+                    &mut d,
+                    &font,
+                    parts[1],
+                    &mut meta_description,
+                    "Description",
+                );
+                tags_input.draw(
+                    &mut d,
+                    &font,
+                    parts[2],
+                    &mut meta_tags,
+                    "Tags (comma separated)",
+                );
+
+                let mut price = obj.conf.price.unwrap_or(0);
+                ds_draw_slider_centered(
+                    &mut d,
+                    &font,
+                    format!("Price: ${}", price).as_str(),
+                    rvec2(rect_midpoint(parts[3]).0, parts[3].y + 16.0),
+                    parts[3].width / 2.0,
+                    20.0,
+                    &mut price,
+                    0.0,
+                    1000.0,
+                    true,
+                );
+                obj.conf.price = if price > 0 { Some(price) } else { None };
+
+                // Placement flags
+                let flags = &mut obj.conf.placement;
+                let toggles = Layout::row(parts[4]).split_even(3);
+                let more_toggles = Layout::row(parts[5]).split_even(3);
+                ds_draw_toggle_rounded(&mut d, &font, Some("floor"), toggles[0], &mut flags.floor);
+                ds_draw_toggle_rounded(
+                    &mut d,
+                    &font,
+                    Some("wall mounted"),
+                    toggles[1],
+                    &mut flags.wall_mounted,
+                );
+                ds_draw_toggle_rounded(
+                    &mut d,
+                    &font,
+                    Some("stackable"),
+                    toggles[2],
+                    &mut flags.stackable,
+                );
+                ds_draw_toggle_rounded(
+                    &mut d,
+                    &font,
+                    Some("player placeable"),
+                    more_toggles[0],
+                    &mut flags.player_placeable,
+                );
+                ds_draw_toggle_rounded(
+                    &mut d,
+                    &font,
+                    Some("draggable"),
+                    more_toggles[1],
+                    &mut flags.draggable,
+                );
+                let layer = obj.conf.layer;
+                let (next_layer, _) = ds_rounded_button(
+                    &mut d,
+                    &font,
+                    more_toggles[2],
+                    Some(format!("layer: {:?}", layer).as_str()),
+                    true,
+                );
+                if next_layer {
+                    obj.conf.layer = layer.next();
+                }
+
+                let (exit_meta, _) = ds_rounded_button(
+                    &mut d,
+                    &font,
+                    anchor_rect(parts[7], 160.0, 30.0, Anchor::Centre),
+                    Some("Exit Metadata Editor"),
+                    true,
+                );
+                if exit_meta {
+                    metadata_mode = false;
+                    let name = meta_display_name.trim();
+                    obj.conf.display_name = if name.is_empty() {
+                        None
+                    } else {
+                        Some(name.to_string())
+                    };
+                    let desc = meta_description.trim();
+                    obj.conf.description = if desc.is_empty() {
+                        None
+                    } else {
+                        Some(desc.to_string())
+                    };
+                    obj.conf.tags = meta_tags
+                        .split(',')
+                        .map(|t| t.trim().to_string())
+                        .filter(|t| !t.is_empty())
+                        .collect();
+                }
+            }
         }
+        focus_end_frame();
         
         // d.draw_texture_pro(&target,
         //                    rrect(0,0, target.width(), -target.height()),
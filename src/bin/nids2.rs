@@ -1,10 +1,20 @@
 #![allow(dead_code, unused_imports, unused_variables, non_snake_case)]
 
 use lazy_static::lazy_static;
+use nids2::clipboard;
+use nids2::history::History;
+use nids2::house::House;
 use nids2::naomi::*;
 use nids2::object::*;
+use nids2::palette::{self, Palettes};
+use nids2::prefab::{self, Prefab};
+use nids2::room::{Room, TileCatalogue};
 use nids2::save::*;
-use nids2::{game, naomi, object, util};
+use nids2::selection::{self, Align, Axis, Selection};
+use nids2::snap::{Guides, Snap};
+use nids2::spatial::SpatialGrid;
+use nids2::viewport::{self, Viewport};
+use nids2::{game, naomi, object, spatial, util};
 use raylib::ffi::Rectangle as ffirect;
 use raylib::prelude::*;
 use std::cell::RefCell;
@@ -16,57 +26,185 @@ use std::rc;
 use std::sync::{atomic, Mutex};
 use std::ops::DerefMut;
 
-/// Type alias because me is lazy
-type GenObj = rc::Rc<RefCell<object::GenericObject>>;
-
-fn save_to_file(fname: &str, objs: &[GenObj], player: &Naomi) {
-    let mut result = objs.to_bytes();
+fn save_to_file(fname: &str, objs: &[GenObj], player: &Naomi, house: &mut House) {
+    // The objects of the current room live in the game loop, hand the house a copy to save
+    house.store_objects(objs.to_vec());
+    let mut result = write_header();
+    result.extend(house.to_bytes());
     result.extend(player.to_bytes().iter());
     fs::write(fname, result).unwrap();
 }
 
+/** Read a save file into the house, the objects of the current room and Naomi. Nothing is changed if
+ * the file can't be read. Files from before saves had a header only hold one room, which becomes the
+ * room the house starts in.
+ */
 fn load_from_file(
     fname: &str,
     objs: &mut Vec<GenObj>,
     player: &mut Naomi,
-) {
-    let mut file = fs::File::open(fname).unwrap();
-    let mut result = Vec::new();
-    let size = file.read_to_end(&mut result).unwrap();
-    let objs_res =
-        Vec::<GenObj>::from_bytes(result.as_slice()).unwrap();
-    let plyr_res = Naomi::from_bytes(&result[objs_res.1..]).unwrap();
-
-    *objs = objs_res.0;
-    *player = plyr_res.0;
+    house: &mut House,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let result = fs::read(fname)?;
+    let header = read_header(result.as_slice())?;
+    let bytes = &result[header.1..];
+    match header.0 {
+        LEGACY_SAVE_VERSION => {
+            let count = usize::from_bytes(bytes)?;
+            let mut bytes_read = count.1;
+            let mut loaded = Vec::new();
+            for _ in 0..count.0 {
+                let obj = GenericObject::from_legacy_bytes(&bytes[bytes_read..])?;
+                bytes_read += obj.1;
+                loaded.push(rc::Rc::new(RefCell::new(obj.0)));
+            }
+            let plyr_res = Naomi::from_legacy_bytes(&bytes[bytes_read..])?;
+
+            // The old room saved its own walls, so they replace the generated ones
+            house.take_objects();
+            *objs = loaded;
+            *player = plyr_res.0;
+        }
+        SAVE_VERSION => {
+            let house_res = House::from_bytes(bytes)?;
+            let plyr_res = Naomi::from_bytes(&bytes[house_res.1..])?;
+
+            *house = house_res.0;
+            *objs = house.take_objects();
+            *player = plyr_res.0;
+        }
+        v => return Err(format!("unsupported save version {}", v).into()),
+    }
     // println!("Loaded {} objects: ", objs.len());
     // for obj in objs.iter() {
     //     println!("\t{}", obj.borrow());
     // }
+    Ok(())
+}
+
+/// Take an object out of `objs` along with everything stacked on it, so they can be moved to another
+/// room together.
+fn take_with_children(objs: &mut Vec<GenObj>, root: &GenObj) -> Vec<GenObj> {
+    let mut ids = vec![root.borrow().get_id()];
+    let mut i = 0;
+    while i < ids.len() {
+        for obj in objs.iter() {
+            let obj = obj.borrow();
+            if obj.parent == Some(ids[i]) && !ids.contains(&obj.get_id()) {
+                ids.push(obj.get_id());
+            }
+        }
+        i += 1;
+    }
+
+    let (taken, kept): (Vec<GenObj>, Vec<GenObj>) = objs
+        .drain(..)
+        .partition(|o| ids.contains(&o.borrow().get_id()));
+    *objs = kept;
+    taken
+}
+
+/// Bake the floor tiles of a room into a texture that can be drawn in one go.
+fn bake_floor(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    room: &Room,
+    catalogue: &TileCatalogue,
+) -> Texture2D {
+    let bckg = room
+        .gen_floor_image(catalogue)
+        .expect("Unable to open tile sprite!");
+    rl.load_texture_from_image(thread, &bckg)
+        .expect("Unable load texture from image!")
+}
+
+/// Point the camera keeps in the middle of the canvas, the centre of Naomi's feet.
+fn focus_point(naomi: &Naomi) -> Vector2 {
+    let rect = naomi.get_collision_rect();
+    rvec2(rect.x + rect.width / 2., rect.y + rect.height / 2.)
+}
+
+/// What the paint tool puts down when clicking, as an index into the tile catalogue.
+#[derive(Clone, Copy, PartialEq)]
+enum Brush {
+    Floor(usize),
+    Wallpaper(usize),
+}
+
+/// How far in screen pixels the cursor may wander between pressing and releasing the button for it
+/// still to count as a click rather than a drag.
+const CLICK_DISTANCE: f32 = 3.;
+
+/// Where an object was before it was dragged, and the colour it had, to put it back if it can't be
+/// dropped. Nothing else is touched, so its animation carries on.
+struct DragStart {
+    obj: GenObj,
+    pos: Position,
+    parent: Option<i32>,
+    parent_offset: Position,
+    colormod: Color,
+}
+
+/// Object being dragged with the mouse, and what is needed to put it back if it can't be dropped.
+struct Drag {
+    obj: GenObj,
+    group: Vec<GenObj>,    // Everything dragged along, including `obj`
+    grab: Vector2,         // Where the cursor holds the object, relative to its position
+    first_pos: Vector2,    // Where the button was pressed, in screen space
+    start: Vec<DragStart>, // The group as it was before the drag
+    carried: bool,         // Whether Naomi was already carrying the object
+    valid: bool,           // Whether the object can be dropped where it is
+}
+
+/** Check whether every object of a group can be set down where it is: inside the room, on the floor
+ * or a wall as its placement flags allow, and not on top of Naomi or anything it can't share space
+ * with. Objects resting on a surface may overlap it.
+ */
+fn drop_is_valid(group: &[GenObj], grid: &SpatialGrid, room: &Room, naomi: &Naomi) -> bool {
+    group.iter().all(|obj| {
+        let (rect, layer) = {
+            let o = obj.borrow();
+            (o.get_collision_rect(), o.get_layer())
+        };
+        let inside = rect.x >= 0.
+            && rect.y >= 0.
+            && rect.x + rect.width <= room.width() as f32
+            && rect.y + rect.height <= room.height() as f32;
+        let on_naomi = layer.collides_with(naomi.get_layer())
+            && naomi.get_collision_rect().check_collision_recs(&rect);
+        let surface = grid.surface_under(obj);
+        let allowed = room.allows_placement(&obj.borrow(), surface.is_some());
+        let mut ignore = group.to_vec();
+        ignore.extend(surface);
+        inside && allowed && !on_naomi && !grid.is_blocked(obj, &ignore)
+    })
 }
 
 #[derive(PartialEq)]
 enum MenuSelections {
     MenuClosed,
-    TypeSelect,
-    ItemSelect,
+    Catalogue,
+    PrefabSelect,
+    SavePrefab,
     ColorSelect,
+    HouseMap,
+    Paint,
     SaveExit,
 }
 
 
 fn main() {
-    let scr_w = 640;
-    let scr_h = 640;
-    let game_w = 320;
-    let game_h = 320;
+    let mut scr_w = 640;
+    let mut scr_h = 640;
     let debug = false;
 
     /* GAME SCREEN AND STATIC INITIALIZATION */
     let (mut rl, thread) = raylib::init()
         .size(scr_w, scr_h)
         .title("Hello, World")
+        .resizable()
         .build();
+    rl.set_window_min_size(viewport::CANVAS_WIDTH, viewport::CANVAS_HEIGHT);
 
     game::init(&mut rl, &thread);
 
@@ -84,255 +222,454 @@ fn main() {
     let mut pause = false;
     let mut exit = false;
     let mut menu_selection = MenuSelections::MenuClosed;
-    let mut opt_selection = 0;
-    let mut opt_scroll_index = 0;
-    let mut selected_item = 0;
-    let mut selected_item_scroll_index = 0;
-    let mut drag: Option<(GenObj, Vector2, Vector2)> = None; // Hold whether or not an object drag was detected
+    let mut drag: Option<Drag> = None; // Hold whether or not an object drag was detected
+    let mut history = History::default(); // Edits that can be undone with Ctrl+Z
+    let mut selected = Selection::new(); // Objects picked with shift-click or a rubber band
+    let mut snap = Snap::new(); // Grid and guides objects line up with when set down
+    let mut band: Option<Vector2> = None; // Where a rubber band selection was started, in the room
+    let mut clip_text = String::new(); // Last copy, for when the system clipboard can't be used
+    let mut brush: Option<Brush> = None; // Paint tool, replaces dragging while active
+    let mut paint_selection = 0;
+    let mut paint_scroll_index = 0;
 
-    /* Constant Object Type Vectors */
+    /* Furniture Catalogue */
     let types_vec = util::get_all_types(true);
+    let mut furniture = util::Catalogue::new(&types_vec);
+
+    /* Saved Groups Of Objects */
+    let mut prefabs = Prefab::load_all(prefab::PREFAB_DIR);
+    let mut prefab_selection = 0;
+    let mut prefab_scroll_index = 0;
+    let mut prefab_name = String::new(); // Name typed in for the prefab being saved
+    let mut prefab_input = util::TextInput::new().max_len(32);
     let sorted_objs = util::get_all_objects_sorted(true);
 
-    /* Color Selection Vector */
-    let color_wheel = vec![
-        Color::WHITE,
-        Color::RAYWHITE,
-        Color::LIGHTGRAY,
-        Color::GRAY,
-        Color::DARKGRAY,
-        Color::BLACK,
-        Color::YELLOW,
-        Color::GOLD,
-        Color::ORANGE,
-        Color::PINK,
-        Color::RED,
-        Color::MAROON,
-        Color::MAGENTA,
-        Color::SKYBLUE,
-        Color::BLUE,
-        Color::DARKBLUE,
-        Color::PURPLE,
-        Color::VIOLET,
-        Color::DARKPURPLE,
-        Color::BEIGE,
-        Color::BROWN,
-        Color::DARKBROWN,
-        Color::DARKGREEN,
-        Color::GREEN,
-        Color::LIME,
-    ];
-    let color_wheel_str: Vec<String> = vec![
-        "White",
-        "RayWhite",
-        "Light Gray",
-        "Gray",
-        "Dark Gray",
-        "Black",
-        "Yellow",
-        "Gold",
-        "Orange",
-        "Pink",
-        "Red",
-        "Maroon",
-        "Magenta",
-        "Sky Blue",
-        "Blue",
-        "Dark Blue",
-        "Purple",
-        "Violet",
-        "Dark Purple",
-        "Beige",
-        "Brown",
-        "Dark Brown",
-        "Dark Green",
-        "Green",
-        "Lime Green",
-    ]
-    .iter()
-    .map(|s| s.to_string())
-    .collect();
+    /* Floor Tiles And Wallpapers For The Paint Tool */
+    let catalogue = TileCatalogue::load("data/tiles.toml").expect("Unable to load tile catalogue!");
+    let paint_brushes: Vec<Brush> = (0..catalogue.tiles.len())
+        .map(Brush::Floor)
+        .chain((0..catalogue.wallpapers.len()).map(Brush::Wallpaper))
+        .collect();
+    let paint_names: Vec<String> = catalogue
+        .tiles
+        .iter()
+        .map(|t| format!("Floor: {}", t.name))
+        .chain(
+            catalogue
+                .wallpapers
+                .iter()
+                .map(|w| format!("Wallpaper: {}", w.name)),
+        )
+        .collect();
 
-    // Generate tiled background at the start of the game program.
-    let background_tiles = {
-        let mut bckg = Image::gen_image_color(game_w, game_h, Color::WHITE);
-        let tile = Image::load_image("data/spr_tile.png").expect("Unable to open tile sprite!");
-        let tile_rect = rrect(0, 0, tile.width(), tile.height());
-        let tile_h = game_w / tile.width();
-        let tile_v = game_h / tile.height();
-        let mut draw_rect = tile_rect;
-
-        for i in 0..tile_v {
-            draw_rect.y = (i * tile.height()) as f32;
-            for j in 0..tile_h {
-                draw_rect.x = (j * tile.width()) as f32;
-                bckg.draw(&tile, tile_rect, draw_rect, Color::WHITE);
-            }
-        }
-
-        rl.load_texture_from_image(&thread, &bckg)
-            .expect("Unable load texture from image!")
-    };
+    /* Colours For Tinting Objects */
+    let mut palettes = Palettes::load_or_default(palette::PALETTE_PATH);
+    let mut color_picker = util::ColorPicker::new();
 
     let mut obj_refactor: Vec<GenObj> = Vec::new();
 
     // Create Naomi Player Object
-    let mut naomi = naomi::Naomi::new(object::Position::new(64, 64), 1, game_w, game_h);
+    let mut naomi = naomi::Naomi::new(object::Position::new(64, 64), 1, 0, 0);
     id_counter += 1;
+
+    // Rooms of the house for a new game, with their walls. A save file brings its own house.
+    let mut house =
+        House::load("data/house.toml", &mut id_counter).expect("Unable to load house layout!");
     
     // Load save file or create appropriate new game setup
     if fs::File::open("data/nids.sav").is_ok() {
         /* Load All Objects */
         println!("Loading nids.sav file...");
         let mut all_obj = Vec::new();
-        load_from_file("data/nids.sav", &mut all_obj, &mut naomi);
+        if let Err(e) = load_from_file("data/nids.sav", &mut all_obj, &mut naomi, &mut house) {
+            // Start over in the new house, the file is written again on Save and Exit
+            println!("Unable to load nids.sav, starting a new game: {}", e);
+            all_obj = house.take_objects();
+        }
         for obj in all_obj.iter() {
             obj_refactor.push(obj.clone());
         }
-        obj_refactor.sort_unstable_by_key(|a| a.borrow().get_depth());
+        obj_refactor.sort_unstable_by_key(|a| a.borrow().get_draw_order());
         
-        // Calculate next valid ID to assign items
-        let mut max_id = -1;
-        for obj in &obj_refactor {
-            if obj.borrow().get_id() > max_id {
-                max_id = obj.borrow().get_id();
-            }
-        }
-        id_counter = max_id + 1;
+        // Calculate next valid ID to assign items, looking through every room
+        id_counter = house.max_id(&obj_refactor) + 1;
 
     } else {
-        //
-        // Create Walls
-        //
-        for x in (32..game_w - 32).step_by(32) {
-            obj_refactor.push(rc::Rc::new(RefCell::new(GenericObject::new(
-                id_counter,
-                0,
-                Some(Position::new(x, 0)),
-            ))));
-
-            id_counter += 1;
-        }
-        for y in (0..game_h).step_by(64) {
-            obj_refactor.push(rc::Rc::new(RefCell::new(GenericObject::new(
-                id_counter,
-                9,
-                Some(Position::new(0, y)),
-            ))));
-            id_counter += 1;
-            obj_refactor.push(rc::Rc::new(RefCell::new(GenericObject::new(
-                id_counter,
-                9,
-                Some(Position::new(game_w - 32, y)),
-            ))));
-            if y == (game_h as f32 * (1.5/3.)) as i32 {
-                for x in 32..(game_w as f32 * (4./7.))as i32 {
-                    obj_refactor.push(rc::Rc::new(RefCell::new(GenericObject::new(
-                        id_counter,
-                        0,
-                        Some(Position::new(x, y)),
-                    ))));
-
-                    id_counter += 1;
-                    
-                }
-            }
-            id_counter += 1;
-        }
+        obj_refactor = house.take_objects();
     }
-    
+
+    // Naomi stays inside the room, whichever room was loaded
+    naomi.scrw = house.room().width();
+    naomi.scrh = house.room().height();
+    let (game_w, game_h) = house.room().size;
+
+    // Generate tiled background at the start of the game program.
+    let mut background_tiles = bake_floor(&mut rl, &thread, house.room(), &catalogue);
+
+    // Bucket objects so that collision and picking don't have to look at every object
+    object::update_attachments(&obj_refactor);
+    let mut grid = SpatialGrid::from_objects(spatial::DEFAULT_CELL_SIZE, &obj_refactor);
+    // Objects that moved since the grid was last brought up to date
+    let mut moved: Vec<GenObj> = Vec::new();
 
     let mut target = rl.load_render_texture(&thread, game_w as u32, game_h as u32).unwrap(); 
 
+    // Scroll around rooms that don't fit in the window, and fit the view to the window
+    let mut viewport = Viewport::new(
+        &mut rl,
+        &thread,
+        viewport::CANVAS_WIDTH,
+        viewport::CANVAS_HEIGHT,
+        rrect(0, 0, game_w, game_h),
+    );
+    viewport.camera.snap_to(focus_point(&naomi));
+
     /* GAME LOOP */
     while !exit {
         frame_no += 1; // Frame counter
 
+        // Keep up with the window being resized or going fullscreen
+        if rl.is_key_pressed(KeyboardKey::KEY_F11) {
+            viewport.toggle_fullscreen(&mut rl);
+        }
+        viewport.update(&rl);
+        scr_w = rl.get_screen_width();
+        scr_h = rl.get_screen_height();
+
+        // Snapping settings
+        if rl.is_key_pressed(KeyboardKey::KEY_F2) {
+            snap.cycle_grid();
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F3) {
+            snap.show_grid = !snap.show_grid;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F4) {
+            snap.guides = !snap.guides;
+        }
+
         // Do Required Actions for all objects on screen
         for obj in obj_refactor.iter() {
             obj.borrow_mut().do_step(frame_no);
+            // Animated objects can change shape with every step
+            if obj.borrow().is_animating() {
+                grid.update(obj);
+            }
         }
+        // What Naomi carries moves with her, and is snapped if she sets it down
+        moved.extend(naomi.select_obj.clone());
         naomi.do_step(frame_no); // Naomi object is updated seperately for drawing reasons
 
         // Pause key
-        if rl.is_key_pressed(KeyboardKey::KEY_P)
+        let typing = menu_selection == MenuSelections::SavePrefab
+            || (menu_selection == MenuSelections::Catalogue && furniture.typing());
+        if (rl.is_key_pressed(KeyboardKey::KEY_P) && !typing)
             || (rl.is_key_pressed(KeyboardKey::KEY_ESCAPE)
                 && menu_selection == MenuSelections::MenuClosed
-                && naomi.select_obj.is_none())
+                && naomi.select_obj.is_none()
+                && brush.is_none())
         {
             pause = !pause;
         }
 
+        // Put the paint tool away. Naomi doesn't get the key press that did it, or she would drop what she
+        // is carrying too.
+        let brush_dropped = brush.is_some()
+            && (rl.is_key_pressed(KeyboardKey::KEY_ESCAPE)
+                || rl.is_mouse_button_pressed(MouseButton::MOUSE_RIGHT_BUTTON));
+        if brush_dropped {
+            brush = None;
+        }
+
         // Handle player input && game logic if game is not paused
         if !pause {
+            // The menu's widgets are gone once it closes, so the keyboard and D-pad go back to Naomi
+            util::focus_clear();
+
             // naomi::handle_input returns an object if one was placed down. This transfers
-            // ownership of the object from naomi to the main object vector
-            if let Some(r) = naomi.handle_input(&mut rl, &mut id_counter, &mut obj_refactor) {
+            // ownership of the object from naomi to the main object vector. The keyboard is left alone
+            // on the frame the paint tool is put away.
+            let placed = if brush_dropped {
+                None
+            } else {
+                naomi.handle_input(
+                    &mut rl,
+                    &mut id_counter,
+                    &mut obj_refactor,
+                    &mut grid,
+                    &mut history,
+                    &snap,
+                    house.room(),
+                )
+            };
+            if let Some(r) = placed {
                 let target_id = r.borrow().get_id();
                 println!("Removing obj {}", r.borrow());
-                obj_refactor.retain(|obj| obj.borrow().get_id() != r.borrow().get_id());
+                grid.remove(&r);
+                selected.remove(&r);
+                obj_refactor.retain(|obj| !rc::Rc::ptr_eq(obj, &r));
+            }
+
+            /* UNDO, REDO AND CLIPBOARD */
+            // Only while nothing is being carried or dragged, so that no edit is half done
+            let ctrl = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
+                || rl.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+            if ctrl && naomi.select_obj.is_none() && drag.is_none() {
+                let done = if rl.is_key_pressed(KeyboardKey::KEY_Z) {
+                    history.undo(&mut obj_refactor, &mut grid)
+                } else if rl.is_key_pressed(KeyboardKey::KEY_Y) {
+                    history.redo(&mut obj_refactor, &mut grid)
+                } else {
+                    Ok(false)
+                };
+                match done {
+                    // Anything in the room may have changed, so everything is looked at again
+                    Ok(true) => {
+                        object::update_attachments(&obj_refactor);
+                        grid.update_all(&obj_refactor);
+                    }
+                    Ok(false) => (),
+                    Err(e) => println!("Unable to undo: {}", e),
+                }
+                selected.retain_existing(&obj_refactor);
+
+                /* COPY AND PASTE */
+                if rl.is_key_pressed(KeyboardKey::KEY_C) && !selected.is_empty() {
+                    clip_text = clipboard::copy(&selected.objects);
+                    if let Err(e) = rl.set_clipboard_text(clip_text.as_str()) {
+                        println!("Unable to copy to the system clipboard: {}", e);
+                    }
+                }
+                let pasted = if rl.is_key_pressed(KeyboardKey::KEY_V) {
+                    // Prefer the system clipboard so objects can come from another game
+                    let at = viewport.screen_to_world(rl.get_mouse_position());
+                    let system = rl.get_clipboard_text().ok().and_then(|text| {
+                        clipboard::paste(text.as_str(), at, &mut id_counter, &snap).ok()
+                    });
+                    match system {
+                        Some(objs) => Ok(objs),
+                        None => clipboard::paste(clip_text.as_str(), at, &mut id_counter, &snap),
+                    }
+                } else if rl.is_key_pressed(KeyboardKey::KEY_D) && !selected.is_empty() {
+                    clipboard::duplicate(&selected.objects, &mut id_counter, &snap)
+                } else {
+                    Ok(Vec::new())
+                };
+                match pasted {
+                    // The copies have to fit where they land, like anything else being set down
+                    Ok(objs) if !drop_is_valid(&objs, &grid, house.room(), &naomi) => {
+                        println!("Unable to paste: there is no room for the objects there");
+                    }
+                    Ok(objs) if !objs.is_empty() => {
+                        // The new objects replace the selection so they can be moved straight away
+                        selected.clear();
+                        for obj in objs.iter() {
+                            grid.insert(obj);
+                            selected.add(obj);
+                        }
+                        history.add_all(&objs);
+                        moved.extend(objs.iter().cloned());
+                        obj_refactor.extend(objs);
+                    }
+                    Ok(_) => (),
+                    Err(e) => println!("Unable to paste: {}", e),
+                }
+            }
+
+            /* SELECTION */
+            // Edit everything that is selected at once
+            if !selected.is_empty() && drag.is_none() {
+                if rl.is_key_pressed(KeyboardKey::KEY_DELETE)
+                    || rl.is_key_pressed(KeyboardKey::KEY_BACKSPACE)
+                {
+                    let removed = selected.delete(&mut obj_refactor, &mut grid, &mut history);
+                    let carried = naomi
+                        .select_obj
+                        .as_ref()
+                        .map_or(false, |o| removed.iter().any(|r| rc::Rc::ptr_eq(o, r)));
+                    if carried {
+                        naomi.select_obj = None;
+                    }
+                }
+                if rl.is_key_pressed(KeyboardKey::KEY_PAGE_UP) {
+                    selected.change_depth(1, &mut history);
+                }
+                if rl.is_key_pressed(KeyboardKey::KEY_PAGE_DOWN) {
+                    selected.change_depth(-1, &mut history);
+                }
+                let aligns = [
+                    (KeyboardKey::KEY_ONE, Align::Left),
+                    (KeyboardKey::KEY_TWO, Align::Right),
+                    (KeyboardKey::KEY_THREE, Align::Top),
+                    (KeyboardKey::KEY_FOUR, Align::Bottom),
+                    (KeyboardKey::KEY_FIVE, Align::CentreX),
+                    (KeyboardKey::KEY_SIX, Align::CentreY),
+                ];
+                for (key, align) in aligns.iter() {
+                    if rl.is_key_pressed(*key) {
+                        selected.align(*align, &mut history);
+                        moved.extend(selected.objects.iter().cloned());
+                    }
+                }
+                if rl.is_key_pressed(KeyboardKey::KEY_SEVEN) {
+                    selected.distribute(Axis::Horizontal, &mut history);
+                    moved.extend(selected.objects.iter().cloned());
+                }
+                if rl.is_key_pressed(KeyboardKey::KEY_EIGHT) {
+                    selected.distribute(Axis::Vertical, &mut history);
+                    moved.extend(selected.objects.iter().cloned());
+                }
             }
         
 
+            /* CAMERA CONTROLS */
+            // The wheel recolours a dragged object instead of zooming
+            viewport.handle_input(&rl, drag.is_none());
+            if naomi.moving {
+                viewport.camera.following = true;
+            }
+
+            /* PAINT TOOL */
+            // Paint floor cells and wall segments under the cursor while the button is held
+            if let Some(b) = brush {
+                if rl.is_mouse_button_down(MouseButton::MOUSE_LEFT_BUTTON) {
+                    let pos = viewport.screen_to_world(rl.get_mouse_position());
+                    match b {
+                        Brush::Floor(tile) => {
+                            let changed = match house.room().cell_at(pos) {
+                                Some((col, row)) => house.room_mut().set_tile(col, row, tile),
+                                None => false,
+                            };
+                            if changed {
+                                background_tiles =
+                                    bake_floor(&mut rl, &thread, house.room(), &catalogue);
+                            }
+                        }
+                        Brush::Wallpaper(paper) => {
+                            let segment = grid
+                                .query_point(pos)
+                                .into_iter()
+                                .filter(|obj| obj.borrow().contains_point(pos))
+                                .filter_map(|obj| house.room().wall_segment_of(&obj.borrow()))
+                                .next();
+                            if let Some(seg) = segment {
+                                house.room_mut().walls[seg].wallpaper = Some(paper);
+                                let tint = catalogue.wallpaper_tint(Some(paper));
+                                for obj in obj_refactor.iter() {
+                                    if house.room().wall_segment_of(&obj.borrow()) == Some(seg) {
+                                        obj.borrow_mut().colormod = tint;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
             /* DRAG GESTURE DETECTION */
             // Pick up an object and move it somewhere
-            if rl.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON)
+            if brush.is_none()
+                && viewport.contains(rl.get_mouse_position())
+                && rl.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON)
             {
-
                 // Determine if mouse landed on selectable item
-                // Select based off of bounding box, or if None, sprite_area.
-                // TODO: Pixel-perfect collisions?
-                let pos = rl.get_mouse_position();
-
-                obj_refactor.iter().rev().find(|&obj| {
-                    if obj
-                        .borrow()
-                        .get_collision_rect()
-                        .check_collision_point_rec(pos)
-                        && !obj.borrow().object_data.1.category.eq("sys")
-                    {
+                // Select the topmost object with a visible pixel under the cursor.
+                let pos = viewport.screen_to_world(rl.get_mouse_position());
+                let shift = rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
+                    || rl.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+
+                let picked = grid
+                    .query_point(pos)
+                    .into_iter()
+                    .filter(|obj| {
+                        obj.borrow().contains_point(pos)
+                            && obj.borrow().object_data.1.placement.draggable
+                    })
+                    .max_by_key(|obj| obj.borrow().get_draw_order());
+                match picked {
+                    Some(obj) if shift => selected.toggle(&obj),
+                    Some(obj) => {
+                        // Dragging a selected object brings the rest of the selection along
+                        if !selected.contains(&obj) {
+                            selected.select_only(&obj);
+                        }
+                        let mut group = selected.movable();
+                        if !group.iter().any(|o| rc::Rc::ptr_eq(o, &obj)) {
+                            group.push(obj.clone());
+                        }
+                        for o in group.iter() {
+                            history.begin(o);
+                        }
+                        let start = group
+                            .iter()
+                            .map(|o| {
+                                let b = o.borrow();
+                                DragStart {
+                                    obj: o.clone(),
+                                    pos: b.pos,
+                                    parent: b.parent,
+                                    parent_offset: b.parent_offset,
+                                    colormod: b.colormod,
+                                }
+                            })
+                            .collect();
+
+                        // Lift the object off whatever it was sitting on
+                        obj.borrow_mut().detach();
+                        let carried = naomi
+                            .select_obj
+                            .as_ref()
+                            .map_or(false, |o| rc::Rc::ptr_eq(o, &obj));
                         if naomi.select_obj.is_none() {
                             naomi.select_obj = Some(obj.clone());
                             naomi.select_obj_type = obj.borrow().obj_id;
                         }
-                        drag = Some((obj.clone(), pos, pos));
-
-                        return true;
+                        let grab = {
+                            let o = obj.borrow();
+                            pos - rvec2(o.pos.x, o.pos.y)
+                        };
+                        // The first position is kept in screen space to tell a click from a drag
+                        drag = Some(Drag {
+                            obj: obj.clone(),
+                            group,
+                            grab,
+                            first_pos: rl.get_mouse_position(),
+                            start,
+                            carried,
+                            valid: true,
+                        });
                     }
-                    false
-                });
+                    // Start a rubber band on empty floor
+                    None => band = Some(pos),
+                }
             }else if rl.is_mouse_button_down(MouseButton::MOUSE_LEFT_BUTTON) {
                 // DRAG DETECTED! 
-                let pos = rl.get_mouse_position();
-                if let Some((obj, old_pos, first_pos)) = &mut drag {
-                    let delta = pos - *old_pos;
-                    if obj.borrow().get_collision_rect().check_collision_point_rec(pos) {
-                        *old_pos = pos; 
-                        let mut obj = obj.borrow_mut();
-                        obj.pos.x += delta.x as i32;
-                        obj.pos.y += delta.y as i32;
-                    }else{
-                        rl.set_mouse_position(*old_pos);
-                        // { // Preserve our original non-mut obj ptr so we can compare it
-                        //     let mut obj = obj.borrow_mut();
-                        //     obj.pos.x -= obj.pos.x % 4;
-                        //     obj.pos.y -= obj.pos.y % 4;
-                        // } // Mut borrow expires here
-                        // // Remove object from naomi's posession once moved.
-                        // if rl.get_mouse_position() != *first_pos {
-                        //     if let Some(nobj) = &naomi.select_obj {
-                        //         if *nobj.borrow() == *obj.borrow() {
-                        //             naomi.select_obj = None;
-                        //         }   
-                        //     }
-                        // }else{ // Object was selected...
-                        //     naomi.grab_object(obj.clone());
-                        // }
-                        // drag = None;
+                let pos = viewport.screen_to_world(rl.get_mouse_position());
+                if let Some(drag) = &mut drag {
+                    let obj = &drag.obj;
+                    // Keep the object under the cursor where it was grabbed, wherever the cursor goes
+                    let at = pos - drag.grab;
+                    let (dx, dy) = {
+                        let o = obj.borrow();
+                        (at.x.round() as i32 - o.pos.x, at.y.round() as i32 - o.pos.y)
+                    };
+                    for o in drag.group.iter() {
+                        let mut o = o.borrow_mut();
+                        o.pos.x += dx;
+                        o.pos.y += dy;
+                    }
+                    let (snap_x, snap_y) = snap.snap_offset(obj, &grid, &drag.group);
+                    for o in drag.group.iter() {
+                        let mut o = o.borrow_mut();
+                        o.pos.x += snap_x;
+                        o.pos.y += snap_y;
                     }
+                    drag.valid = drop_is_valid(&drag.group, &grid, house.room(), &naomi);
+                    moved.extend(drag.group.iter().cloned());
 
+                    // The wheel goes through the colours of the palette open in the colour picker
+                    let color_wheel = palettes.colors(color_picker.palette);
                     let scroll = rl.get_mouse_wheel_move();
-                    if scroll != 0. {
+                    if scroll != 0. && !color_wheel.is_empty() {
                         let target_clr = obj.borrow().colormod;
                         let mut clr_pos: i32 = color_wheel.iter().position(|&c| c == target_clr).unwrap_or(0) as i32;
                         if scroll < 0. {
@@ -350,45 +687,168 @@ fn main() {
                     }
                 }
             }else {
-                if let Some((obj, old_pos, first_pos)) = drag {
-                    { // Preserve our original non-mut obj ptr so we can compare it
-                        let mut obj = obj.borrow_mut();
-                        obj.pos.x -= obj.pos.x % 4;
-                        obj.pos.y -= obj.pos.y % 4;
-                    } // Mut borrow expires here
-                    // Remove object from naomi's posession once moved.
-                    if rl.get_mouse_position() != first_pos {
-                        if let Some(nobj) = &naomi.select_obj {
-                            if *nobj.borrow() == *obj.borrow() {
-                                naomi.select_obj = None;
-                            }   
-                        }
-                    }else{ // Object was selected...
+                // Select everything the rubber band was dragged over
+                if let Some(start) = band.take() {
+                    let shift = rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
+                        || rl.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+                    let end = viewport.screen_to_world(rl.get_mouse_position());
+                    selected.select_rect(&obj_refactor, selection::rect_between(start, end), shift);
+                }
+
+                if let Some(Drag {
+                    obj,
+                    mut group,
+                    first_pos,
+                    start,
+                    carried,
+                    valid,
+                    ..
+                }) = drag.take()
+                {
+                    let is_naomis = naomi
+                        .select_obj
+                        .as_ref()
+                        .map_or(false, |o| rc::Rc::ptr_eq(o, &obj));
+                    if rl.get_mouse_position().distance_to(first_pos) <= CLICK_DISTANCE {
+                        // Object was selected...
                         naomi.grab_object(obj.clone());
+                    } else if valid {
+                        // Set the object down on any surface it was dropped over
+                        if let Some(parent) = grid.surface_under(&obj) {
+                            obj.borrow_mut().attach_to(&parent.borrow());
+                        }
+                        // Remove object from naomi's posession once moved.
+                        if is_naomis {
+                            naomi.select_obj = None;
+                        }
+                    } else {
+                        // Nowhere to put it, so everything goes back where it was
+                        for s in start.iter() {
+                            let mut o = s.obj.borrow_mut();
+                            o.pos = s.pos;
+                            o.parent = s.parent;
+                            o.parent_offset = s.parent_offset;
+                            o.colormod = s.colormod;
+                            o.update_depth();
+                        }
+                        if is_naomis && !carried {
+                            naomi.select_obj = None;
+                        }
                     }
-                    drag = None;
+                    moved.extend(group.iter().cloned());
+                    // Carried objects are recorded once Naomi sets them down
+                    if let Some(carried) = &naomi.select_obj {
+                        group.retain(|o| !rc::Rc::ptr_eq(o, carried));
+                    }
+                    history.commit_all(&group);
                 }
             }
         }
         
-        // Sort objects by depth. Unstable is better for nearly-sorted lists, which this is.
-        obj_refactor.sort_unstable_by_key(|a| a.borrow().get_depth());
+        /* ROOM TRANSITIONS */
+        // Walking into a door leads to the room on the other side
+        let door = if naomi.moving || drag.is_some() {
+            None
+        } else {
+            house.door_at(naomi.get_collision_rect())
+        };
+        if let Some(door) = door {
+            let from = house.room().name.clone();
+
+            // Whatever Naomi is carrying comes along, including anything stacked on it
+            let carried = match &naomi.select_obj {
+                Some(o) => take_with_children(&mut obj_refactor, o),
+                None => Vec::new(),
+            };
+
+            obj_refactor = match house.go_through(door, std::mem::take(&mut obj_refactor)) {
+                Ok(objs) | Err(objs) => objs,
+            };
+            obj_refactor.extend(carried);
+
+            // Step in through the door leading back to where Naomi came from
+            let b_box = naomi
+                .base
+                .b_box
+                .unwrap_or_else(|| rrect(0, 0, naomi.base.width(), naomi.base.height()));
+            let entrance = house
+                .room()
+                .door_to(&from)
+                .and_then(|d| house.room().entrance_for(d, b_box));
+            naomi.base.pos = entrance.unwrap_or_else(|| Position::new(64, 64));
+            naomi.base.update_depth();
+            naomi.scrw = house.room().width();
+            naomi.scrh = house.room().height();
+            if let Some(o) = &naomi.select_obj {
+                naomi.grab_object(o.clone());
+            }
+
+            // Swap out everything that depends on the room
+            background_tiles = bake_floor(&mut rl, &thread, house.room(), &catalogue);
+            target = rl
+                .load_render_texture(
+                    &thread,
+                    house.room().width() as u32,
+                    house.room().height() as u32,
+                )
+                .unwrap();
+            object::update_attachments(&obj_refactor);
+            grid = SpatialGrid::from_objects(spatial::DEFAULT_CELL_SIZE, &obj_refactor);
+            moved.clear();
+            history.clear(); // Edits are kept per visit, the objects they refer to stayed behind
+            selected.clear();
+            viewport.camera.set_bounds(
+                rrect(0, 0, house.room().width(), house.room().height()),
+                focus_point(&naomi),
+            );
+        }
+
+        // Keep Naomi in view
+        viewport.camera.follow(focus_point(&naomi));
+
+        // Bring stacked objects along with whatever they are sitting on, and re-bucket everything that
+        // moved this frame. Objects taken out of the room this frame stay out.
+        moved.extend(naomi.select_obj.clone());
+        let followers = object::move_attached(&obj_refactor, &moved);
+        for obj in moved.drain(..).chain(followers) {
+            if grid.contains(&obj) {
+                grid.update(&obj);
+            }
+        }
+
+        // Sort objects by layer, then depth. Unstable is better for nearly-sorted lists, which this is.
+        obj_refactor.sort_unstable_by_key(|a| a.borrow().get_draw_order());
 
         /* DRAW SECTION */
+        let mouse_world = viewport.screen_to_world(rl.get_mouse_position());
+        // Show the guides a dragged or carried object would line up with if set down now
+        let guides = match (&drag, &naomi.select_obj) {
+            (Some(drag), _) => {
+                snap.find_guides(drag.obj.borrow().get_collision_rect(), &grid, &drag.group)
+            }
+            (None, Some(obj)) => {
+                snap.find_guides(obj.borrow().get_collision_rect(), &grid, &[obj.clone()])
+            }
+            (None, None) => Guides::default(),
+        };
+        let (room_w, room_h) = (target.width(), target.height());
         let mut d = rl.begin_drawing(&thread);
+        util::focus_begin_frame(&d);
         {
             let mut d = d.begin_texture_mode(&thread, &mut target);
 
             // Draw floor
             d.draw_texture(&background_tiles, 0, 0, Color::WHITE);
+            snap.draw_grid(&mut d, room_w, room_h);
 
             {
                 // Draw all objects onto the screen. Naomi object gets drawn at the correct depth
-                let target_depth = naomi.get_depth();
+                // within her layer
+                let target_order = naomi.get_draw_order();
                 let mut naomi_drawn = false;
                 for obj in obj_refactor.iter() {
                     // Ensure naomi is drawn once, and first at it's depth.
-                    if !naomi_drawn && obj.borrow().get_depth() >= target_depth {
+                    if !naomi_drawn && obj.borrow().get_draw_order() >= target_order {
                         naomi.draw(&mut d, debug);
                         naomi_drawn = true;
                     }
@@ -398,68 +858,135 @@ fn main() {
                     naomi.draw(&mut d, debug);
                 }
             }
+
+            // Show what is selected on top of everything
+            snap.draw_guides(&mut d, &guides);
+            // Show whether a dragged object can be dropped where it is
+            if let Some(drag) = &drag {
+                let color = if drag.valid { Color::LIME } else { Color::RED };
+                for o in drag.group.iter() {
+                    let rect = o.borrow().get_collision_rect();
+                    if !drag.valid {
+                        d.draw_rectangle_rec(rect, color.fade(0.35));
+                    }
+                    d.draw_rectangle_lines_ex(rect, 1, color);
+                }
+            }
+            selected.draw_outlines(&mut d);
+            if let Some(start) = band {
+                selection::draw_band(&mut d, start, mouse_world);
+            }
         }
         
-        // Draw render target
-        d.draw_texture_pro(&target,
-                           rrect(0,0, target.width(), -target.height()),
-                           rrect(0,0, scr_w, scr_h),
-                           rvec2(0,0),
-                           0.,
-                           Color::WHITE);
-        
-        /* PAUSE MENU SECTION */
-        if pause {
-            let menu_height = 128;
+        // Draw render target onto the canvas through the camera. Anything outside the room is left black.
+        let cam = viewport.camera.camera2d();
+        {
+            let mut d = d.begin_texture_mode(&thread, &mut viewport.canvas);
+            d.clear_background(Color::BLACK);
+            let mut d = d.begin_mode2D(cam);
+            d.draw_texture_rec(
+                &target,
+                rrect(0, 0, target.width(), -target.height()),
+                rvec2(0, 0),
+                Color::WHITE,
+            );
+        }
 
-            /* Draw Base Buttons */
-            let (furn_button, furn_vec) = util::ds_rounded_button_centered(
-                &mut d,
+        // Scale the canvas up to fit the window
+        viewport.draw(&mut d);
+
+        // Show what the paint tool is loaded with
+        if let Some(b) = brush {
+            let name = paint_brushes
+                .iter()
+                .position(|&p| p == b)
+                .and_then(|i| paint_names.get(i))
+                .map_or("", |n| n.as_str());
+            d.draw_text_ex(
                 &font,
-                rrect(
-                    scr_w as f32 * 0.25,
-                    scr_h - menu_height / 2,
-                    scr_w / 4,
-                    menu_height / 2,
-                ),
-                Some("Select Furniture"),
-                true,
+                format!("{} (right click to stop)", name).as_str(),
+                rvec2(8, 8),
+                16.,
+                1.,
+                Color::BLACK,
             );
-            let (clr_button, clr_vec) = util::ds_rounded_button_centered(
-                &mut d,
+        }
+        
+        // Show the grid size while the grid is on screen
+        if snap.show_grid && !pause {
+            d.draw_text_ex(
                 &font,
-                rrect(
-                    scr_w as f32 * 0.5,
-                    scr_h - menu_height / 2,
-                    scr_w / 4,
-                    menu_height / 2,
-                ),
-                Some("Color Selection"),
-                true,
+                format!("Grid: {} (F2)", snap.grid_name()).as_str(),
+                rvec2(8, scr_h - 24),
+                16.,
+                1.,
+                Color::BLACK,
             );
+        }
 
-            let (exit_button, exit_vec) = util::ds_rounded_button_centered(
+        /* PAUSE MENU SECTION */
+        if pause {
+            let menu_height = 128.;
+            // Screen split into the room above the bar, the bar of buttons, and a margin below it
+            let rows = util::Layout::column(rrect(0, 0, scr_w, scr_h)).split(&[
+                util::Size::Fill(1.),
+                util::Size::Fixed(menu_height / 2.),
+                util::Size::Fixed(menu_height / 4.),
+            ]);
+            let bar = util::Layout::row(rows[1]).split_even(6);
+            // Small submenus open upwards from the bottom of the room area, lined up with their button
+            let above = |button: Rectangle, width: f32, height: f32| {
+                let area = rrect(
+                    button.x,
+                    rows[0].y,
+                    rows[0].x + rows[0].width - button.x,
+                    rows[0].height,
+                );
+                util::anchor_rect(area, width, height, util::Anchor::BottomLeft)
+            };
+            let submenu_width = scr_w as f32 / 4.;
+            let list_height = 3. * 30. + 12.; // Three entries
+
+            /* Draw Base Buttons */
+            let (furn_button, _) =
+                util::ds_rounded_button(&mut d, &font, bar[0], Some("Select Furniture"), true);
+            let (clr_button, clr_vec) =
+                util::ds_rounded_button(&mut d, &font, bar[1], Some("Color Selection"), true);
+            let (prefab_button, _) = util::ds_rounded_button(
                 &mut d,
                 &font,
-                rrect(
-                    scr_w as f32 * 0.75,
-                    scr_h - menu_height / 2,
-                    scr_w / 4,
-                    menu_height / 2,
-                ),
-                Some("Save and Exit"),
-                true,
+                bar[2],
+                Some("Save Prefab"),
+                !selected.is_empty(),
             );
+            let (map_button, _) =
+                util::ds_rounded_button(&mut d, &font, bar[3], Some("House Map"), true);
+            let (paint_button, _) =
+                util::ds_rounded_button(&mut d, &font, bar[4], Some("Paint"), true);
+            let (exit_button, exit_vec) =
+                util::ds_rounded_button(&mut d, &font, bar[5], Some("Save and Exit"), true);
 
             /* Handle Button Returns */
             if furn_button {
                 menu_selection = if menu_selection == MenuSelections::MenuClosed {
-                    MenuSelections::TypeSelect
+                    furniture.open();
+                    MenuSelections::Catalogue
                 } else {
                     MenuSelections::MenuClosed
                 };
             } else if clr_button {
+                color_picker.set_color(naomi.colormod);
                 menu_selection = MenuSelections::ColorSelect;
+            } else if prefab_button {
+                menu_selection = MenuSelections::SavePrefab;
+            } else if map_button {
+                menu_selection = if menu_selection == MenuSelections::HouseMap {
+                    MenuSelections::MenuClosed
+                } else {
+                    MenuSelections::HouseMap
+                };
+            } else if paint_button {
+                menu_selection = MenuSelections::Paint;
             } else if exit_button {
                 menu_selection = MenuSelections::SaveExit;
             }
@@ -468,9 +995,12 @@ fn main() {
             /* Switch Menus With Keyboard Keys */
             if d.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
                 match menu_selection {
-                    MenuSelections::ItemSelect => menu_selection = MenuSelections::TypeSelect,
-                    MenuSelections::TypeSelect => menu_selection = MenuSelections::MenuClosed,
+                    MenuSelections::PrefabSelect => menu_selection = MenuSelections::Catalogue,
+                    MenuSelections::SavePrefab => menu_selection = MenuSelections::MenuClosed,
+                    MenuSelections::Catalogue => menu_selection = MenuSelections::MenuClosed,
                     MenuSelections::ColorSelect => menu_selection = MenuSelections::MenuClosed,
+                    MenuSelections::HouseMap => menu_selection = MenuSelections::MenuClosed,
+                    MenuSelections::Paint => menu_selection = MenuSelections::MenuClosed,
                     MenuSelections::SaveExit => menu_selection = MenuSelections::MenuClosed,
                     MenuSelections::MenuClosed => (), // Already processed KEY_ESCAPE, don't process here.
                 }
@@ -478,64 +1008,154 @@ fn main() {
 
             /* Draw The Proper Submenu */
             match menu_selection {
-                MenuSelections::TypeSelect => {
-                    if util::ds_scroll_selection_auto(
+                MenuSelections::Catalogue => {
+                    // Prefabs have no sprite of their own, so they keep their list behind a button under
+                    // the catalogue
+                    let parts = util::Layout::column(util::inset(rows[0], 32.))
+                        .spacing(8.)
+                        .split(&[util::Size::Fill(1.), util::Size::Fixed(40.)]);
+                    let picked = furniture.draw(&mut d, &font, parts[0], &sorted_objs);
+                    let (prefab_list_button, _) = util::ds_rounded_button(
                         &mut d,
                         &font,
-                        rrect(furn_vec.x, furn_vec.y, scr_w / 4, 0),
-                        3,
-                        &types_vec,
-                        &mut opt_selection,
-                        &mut opt_scroll_index,
-                    ) {
-                        menu_selection = MenuSelections::ItemSelect;
+                        util::anchor_rect(parts[1], submenu_width, 40., util::Anchor::Right),
+                        Some(prefab::PREFAB_CATEGORY),
+                        !prefabs.is_empty(),
+                    );
+                    if let Some(id) = picked {
+                        naomi.select_obj_type = id;
+                        menu_selection = MenuSelections::MenuClosed;
+                    } else if prefab_list_button {
+                        menu_selection = MenuSelections::PrefabSelect;
                     }
                 }
-                MenuSelections::ItemSelect => {
-                    let vec_ref = sorted_objs
-                        .get(types_vec.get(opt_selection as usize).unwrap())
-                        .unwrap();
-                    let mut name_vec = Vec::new();
-                    for item in vec_ref.iter() {
-                        name_vec.push(item.1.name.clone());
-                    }
-                    if util::ds_scroll_selection_auto(
+                MenuSelections::PrefabSelect => {
+                    let name_vec: Vec<String> = prefabs.iter().map(|p| p.name.clone()).collect();
+                    if util::ds_scroll_selection(
                         &mut d,
                         &font,
-                        rrect(furn_vec.x, furn_vec.y, scr_w / 4, 0),
-                        3,
+                        above(bar[0], submenu_width, list_height),
                         &name_vec,
-                        &mut selected_item,
-                        &mut selected_item_scroll_index,
+                        &mut prefab_selection,
+                        &mut prefab_scroll_index,
                     ) {
-                        naomi.select_obj_type = vec_ref.get(selected_item as usize).unwrap().1.id;
-                        menu_selection = MenuSelections::MenuClosed;
+                        // Put the whole prefab down in front of Naomi, selected so it can be moved as one
+                        let facing = naomi.facing_rect();
+                        let at = rvec2(facing.x + facing.width / 2., facing.y + facing.height / 2.);
+                        let spawned = prefabs
+                            .get(prefab_selection as usize)
+                            .map(|p| p.spawn(at, &mut id_counter, &snap));
+                        match spawned {
+                            // It has to fit in front of Naomi, like anything else being set down
+                            Some(Ok(objs)) if drop_is_valid(&objs, &grid, house.room(), &naomi) => {
+                                selected.clear();
+                                for obj in objs.iter() {
+                                    grid.insert(obj);
+                                    selected.add(obj);
+                                }
+                                history.add_all(&objs);
+                                moved.extend(objs.iter().cloned());
+                                obj_refactor.extend(objs);
+                                menu_selection = MenuSelections::MenuClosed;
+                                pause = false;
+                            }
+                            Some(Ok(_)) => {
+                                println!("Unable to place prefab: there is no room for it there")
+                            }
+                            Some(Err(e)) => println!("Unable to place prefab: {}", e),
+                            None => (),
+                        }
+                    }
+                }
+                MenuSelections::SavePrefab => {
+                    let form = util::Layout::column(above(bar[2], submenu_width, 128.))
+                        .padding(4.)
+                        .spacing(8.)
+                        .split(&[util::Size::Fixed(72.), util::Size::Fixed(40.)]);
+                    prefab_input.focus(); // The only box on screen, so it always takes the keyboard
+                    prefab_input.draw(&mut d, &font, form[0], &mut prefab_name, "Prefab Name");
+                    let (save_button, _) = util::ds_rounded_button(
+                        &mut d,
+                        &font,
+                        form[1],
+                        Some("Save"),
+                        !prefab_name.trim().is_empty() && !selected.is_empty(),
+                    );
+                    let submitted = d.is_key_pressed(KeyboardKey::KEY_ENTER)
+                        && !prefab_name.trim().is_empty()
+                        && !selected.is_empty();
+                    if save_button || submitted {
+                        let prefab = Prefab::from_objects(prefab_name.trim(), &selected.objects);
+                        match prefab.save(prefab::PREFAB_DIR) {
+                            Ok(path) => {
+                                println!("Saved prefab to {}", path);
+                                prefabs = Prefab::load_all(prefab::PREFAB_DIR);
+                                prefab_name.clear();
+                                menu_selection = MenuSelections::MenuClosed;
+                            }
+                            Err(e) => println!("Unable to save prefab: {}", e),
+                        }
                     }
                 }
                 MenuSelections::ColorSelect => {
-                    if util::ds_scroll_selection_auto(
+                    // Above the colour button, kept on screen
+                    let (w, h) = (360., 340.);
+                    let picker_rect = rrect(
+                        clr_vec.x.min(scr_w as f32 - w).max(0.),
+                        (rows[0].height - h).max(0.),
+                        w,
+                        h.min(rows[0].height),
+                    );
+                    let (picked, changed) = util::ds_color_picker(
                         &mut d,
                         &font,
-                        rrect(clr_vec.x, clr_vec.y, scr_w / 4, 0),
-                        3,
-                        &color_wheel_str,
-                        &mut opt_selection,
-                        &mut opt_scroll_index,
-                    ) {
-                        naomi.colormod = color_wheel[opt_selection as usize];
+                        picker_rect,
+                        &mut color_picker,
+                        &mut palettes,
+                    );
+                    if changed {
+                        if let Err(e) = palettes.save(palette::PALETTE_PATH) {
+                            println!("Unable to save palettes: {}", e);
+                        }
+                    }
+                    if let Some(color) = picked {
+                        naomi.colormod = color;
                         if let Some(o) = &naomi.select_obj {
                             o.borrow_mut().colormod = naomi.colormod;
                             menu_selection = MenuSelections::MenuClosed;
                         }
+                        if !selected.is_empty() {
+                            selected.recolour(naomi.colormod, &mut history);
+                            menu_selection = MenuSelections::MenuClosed;
+                        }
+                    }
+                }
+                MenuSelections::HouseMap => {
+                    house.draw_map(&mut d, &font, util::inset(rows[0], 32.));
+                }
+                MenuSelections::Paint => {
+                    if util::ds_scroll_selection(
+                        &mut d,
+                        &font,
+                        above(bar[4], submenu_width, list_height),
+                        &paint_names,
+                        &mut paint_selection,
+                        &mut paint_scroll_index,
+                    ) {
+                        // Pick up the brush and get back to the room to paint
+                        brush = paint_brushes.get(paint_selection as usize).copied();
+                        menu_selection = MenuSelections::MenuClosed;
+                        pause = false;
                     }
                 }
                 MenuSelections::SaveExit => {
-                    save_to_file("data/nids.sav", &obj_refactor, &naomi);
+                    save_to_file("data/nids.sav", &obj_refactor, &naomi, &mut house);
                     exit = true;
                 }
                 _ => (),
             };
         }
+        util::focus_end_frame();
     }
 
     // Clean up initialized memory
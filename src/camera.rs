@@ -0,0 +1,166 @@
+//! # Camera
+//! `camera` decides which part of the room is shown in the window. It follows Naomi around, stays inside
+//! the room, and can be zoomed with the mouse wheel or panned by dragging with the middle mouse button.
+//! Positions called "screen" positions here are on the canvas the camera draws to, see `crate::viewport`.
+
+use raylib::prelude::*;
+
+/// Zoom used when a room is first shown, one room pixel per canvas pixel.
+pub const DEFAULT_ZOOM: f32 = 1.0;
+
+/// How far out the camera can be zoomed.
+pub const MIN_ZOOM: f32 = 0.5;
+
+/// How far in the camera can be zoomed.
+pub const MAX_ZOOM: f32 = 4.0;
+
+/// Factor the zoom changes by for each step of the mouse wheel.
+pub const ZOOM_STEP: f32 = 1.1;
+
+/// Fraction of the distance to the followed point that is covered each frame.
+pub const FOLLOW_SPEED: f32 = 0.15;
+
+/** View into a room. `target` is the point of the room shown in the middle of the screen and `zoom` is how
+ * many screen pixels one room pixel takes up.
+ */
+pub struct Camera {
+    pub target: Vector2,
+    pub zoom: f32,
+    pub screen: Vector2,
+    pub bounds: Rectangle,
+    pub following: bool,
+    pan: Option<Vector2>,
+}
+
+impl Camera {
+    /** Create a camera showing a `screen_w`×`screen_h` window into a room covering `bounds`.
+     */
+    pub fn new(screen_w: i32, screen_h: i32, bounds: Rectangle) -> Self {
+        let mut cam = Self {
+            target: rvec2(bounds.x + bounds.width / 2., bounds.y + bounds.height / 2.),
+            zoom: DEFAULT_ZOOM,
+            screen: rvec2(screen_w, screen_h),
+            bounds,
+            following: true,
+            pan: None,
+        };
+        cam.clamp();
+        cam
+    }
+
+    /** Size of the part of the room that fits on screen.
+     */
+    pub fn view_size(&self) -> Vector2 {
+        rvec2(self.screen.x / self.zoom, self.screen.y / self.zoom)
+    }
+
+    /** The part of the room that is on screen.
+     */
+    pub fn view_rect(&self) -> Rectangle {
+        let size = self.view_size();
+        rrect(
+            self.target.x - size.x / 2.,
+            self.target.y - size.y / 2.,
+            size.x,
+            size.y,
+        )
+    }
+
+    /** Keep the view inside the room. A room smaller than the view is centred instead.
+     */
+    pub fn clamp(&mut self) {
+        let half = self.view_size() / 2.;
+        self.target.x = clamp_axis(self.target.x, half.x, self.bounds.x, self.bounds.width);
+        self.target.y = clamp_axis(self.target.y, half.y, self.bounds.y, self.bounds.height);
+    }
+
+    /** Show a different room. The camera jumps straight to `point` rather than sliding across.
+     */
+    pub fn set_bounds(&mut self, bounds: Rectangle, point: Vector2) {
+        self.bounds = bounds;
+        self.pan = None;
+        self.following = true;
+        self.snap_to(point);
+    }
+
+    /** Centre the camera on `point` immediately.
+     */
+    pub fn snap_to(&mut self, point: Vector2) {
+        self.target = point;
+        self.clamp();
+    }
+
+    /** Move the camera part of the way towards `point`. Does nothing while the camera has been panned away.
+     */
+    pub fn follow(&mut self, point: Vector2) {
+        if self.following {
+            self.target += (point - self.target) * FOLLOW_SPEED;
+            self.clamp();
+        }
+    }
+
+    /** Zoom by `steps` wheel steps, keeping the room point under `screen_pos` in place.
+     */
+    pub fn zoom_at(&mut self, screen_pos: Vector2, steps: f32) {
+        let anchor = self.screen_to_world(screen_pos);
+        self.zoom = (self.zoom * ZOOM_STEP.powf(steps)).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.target += anchor - self.screen_to_world(screen_pos);
+        self.clamp();
+    }
+
+    /** Pan with the middle mouse button and zoom with the wheel. `mouse` is the cursor position on the
+     * canvas. The wheel is left alone when `zoom` is false so that it can be used for something else.
+     */
+    pub fn handle_input(&mut self, rl: &RaylibHandle, mouse: Vector2, zoom: bool) {
+        if rl.is_mouse_button_down(MouseButton::MOUSE_MIDDLE_BUTTON) {
+            if let Some(last) = self.pan {
+                // Drag the room along with the cursor
+                self.target -= (mouse - last) / self.zoom;
+                self.following = false;
+                self.clamp();
+            }
+            self.pan = Some(mouse);
+        } else {
+            self.pan = None;
+        }
+
+        if zoom {
+            let wheel = rl.get_mouse_wheel_move();
+            if wheel != 0. {
+                self.zoom_at(mouse, wheel);
+            }
+        }
+    }
+
+    /** Convert a position on screen to a position in the room.
+     */
+    pub fn screen_to_world(&self, pos: Vector2) -> Vector2 {
+        (pos - self.screen / 2.) / self.zoom + self.target
+    }
+
+    /** Convert a position in the room to a position on screen.
+     */
+    pub fn world_to_screen(&self, pos: Vector2) -> Vector2 {
+        (pos - self.target) * self.zoom + self.screen / 2.
+    }
+
+    /** Camera to hand to raylib when drawing the room.
+     */
+    pub fn camera2d(&self) -> Camera2D {
+        Camera2D {
+            offset: self.screen / 2.,
+            target: self.target,
+            rotation: 0.,
+            zoom: self.zoom,
+        }
+    }
+}
+
+/// Clamp one axis of the camera target so that a view `half` wide each way stays within `start..start + len`.
+fn clamp_axis(target: f32, half: f32, start: f32, len: f32) -> f32 {
+    if len <= half * 2. {
+        start + len / 2.
+    } else {
+        target.clamp(start + half, start + len - half)
+    }
+}
@@ -0,0 +1,160 @@
+//! # Clipboard
+//! `clipboard` turns objects into text that can be put on the system clipboard and back again, so that
+//! objects can be copied, pasted and duplicated, even between two running games.
+//!
+//! The text starts with `CLIPBOARD_HEADER` on its own line, followed by one line per object holding its
+//! save bytes written out as hex.
+
+use crate::object::*;
+use crate::save::*;
+use crate::snap::Snap;
+use crate::util::rect_union;
+use raylib::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::rc::Rc;
+
+/// First line of clipboard text holding objects. Text without it is ignored when pasting.
+pub const CLIPBOARD_HEADER: &str = "nids2-objects 1";
+
+/// Smallest amount of bytes an object is saved as: the fixed size fields, no parent and no state.
+const OBJECT_RECORD_MIN: usize = 48 + 4 + 8 + 4;
+
+/// How far a duplicate is put from the objects it was copied from.
+pub const DUPLICATE_OFFSET: i32 = 16;
+
+/** Write objects out as clipboard text.
+ */
+pub fn copy(objs: &[GenObj]) -> String {
+    let mut text = String::from(CLIPBOARD_HEADER);
+    for obj in objs.iter() {
+        text.push('\n');
+        text.push_str(to_hex(&obj.borrow().to_bytes()).as_str());
+    }
+    text
+}
+
+/** Read objects back from clipboard text, exactly as they were copied.
+ */
+pub fn parse(text: &str) -> Result<Vec<GenericObject>, Box<dyn Error>> {
+    let mut lines = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+    if lines.next() != Some(CLIPBOARD_HEADER) {
+        return Err("clipboard doesn't hold any objects".into());
+    }
+    // The text may come from another game or anywhere else, so every line has to be exactly one object
+    // of a type this game knows, with a sprite it has, and no two objects may share an ID or parents
+    // couldn't be told apart
+    let mut objs: Vec<GenericObject> = Vec::new();
+    for line in lines {
+        let bytes = from_hex(line)?;
+        if bytes.len() < OBJECT_RECORD_MIN {
+            return Err("invalid clipboard line".into());
+        }
+        let obj = GenericObject::from_bytes(&bytes)?;
+        if obj.1 != bytes.len() {
+            return Err("invalid clipboard line".into());
+        }
+        if objs.iter().any(|o| o.get_id() == obj.0.get_id()) {
+            return Err(format!("object ID {} is on the clipboard twice", obj.0.get_id()).into());
+        }
+        objs.push(obj.0);
+    }
+    Ok(objs)
+}
+
+/** Make new objects from clipboard text, centred on `at`. See `place()`.
+ */
+pub fn paste(
+    text: &str,
+    at: Vector2,
+    next_id: &mut i32,
+    snap: &Snap,
+) -> Result<Vec<GenObj>, Box<dyn Error>> {
+    Ok(place(parse(text)?, at, next_id, snap))
+}
+
+/** Turn copies of objects into new objects centred on `at`. They are moved by a whole number of `snap`
+ * grid steps so that objects lined up on the grid stay lined up. Each object gets a fresh ID from
+ * `next_id`. Objects that were sitting on another copied object stay on its copy, the rest are set down
+ * on the floor.
+ */
+pub fn place(
+    mut objs: Vec<GenericObject>,
+    at: Vector2,
+    next_id: &mut i32,
+    snap: &Snap,
+) -> Vec<GenObj> {
+    let bounds = match bounds_of(&objs) {
+        Some(b) => b,
+        None => return Vec::new(),
+    };
+    let dx = snap.snap_value((at.x - (bounds.x + bounds.width / 2.)).round() as i32);
+    let dy = snap.snap_value((at.y - (bounds.y + bounds.height / 2.)).round() as i32);
+
+    let new_ids: HashMap<i32, i32> = objs
+        .iter()
+        .map(|o| {
+            let id = *next_id;
+            *next_id += 1;
+            (o.get_id(), id)
+        })
+        .collect();
+
+    for obj in objs.iter_mut() {
+        let id = new_ids[&obj.get_id()];
+        obj.set_id(id);
+        match obj.parent.and_then(|p| new_ids.get(&p)) {
+            Some(&parent) => obj.parent = Some(parent),
+            None => {
+                obj.detach();
+            }
+        }
+        obj.pos.x += dx;
+        obj.pos.y += dy;
+        obj.update_depth();
+    }
+
+    objs.into_iter().map(|o| Rc::new(RefCell::new(o))).collect()
+}
+
+/** Make copies of objects a little below and to the right of them. See `paste()`.
+ */
+pub fn duplicate(
+    objs: &[GenObj],
+    next_id: &mut i32,
+    snap: &Snap,
+) -> Result<Vec<GenObj>, Box<dyn Error>> {
+    let rects: Vec<Rectangle> = objs.iter().map(|o| o.borrow().get_obj_rect()).collect();
+    let bounds = match rect_union(&rects) {
+        Some(b) => b,
+        None => return Ok(Vec::new()),
+    };
+    let at = rvec2(
+        bounds.x + bounds.width / 2. + DUPLICATE_OFFSET as f32,
+        bounds.y + bounds.height / 2. + DUPLICATE_OFFSET as f32,
+    );
+    paste(copy(objs).as_str(), at, next_id, snap)
+}
+
+/// Rectangle around the sprites of some objects.
+fn bounds_of(objs: &[GenericObject]) -> Option<Rectangle> {
+    let rects: Vec<Rectangle> = objs.iter().map(|o| o.get_obj_rect()).collect();
+    rect_union(&rects)
+}
+
+/// Write bytes out as lowercase hex, two digits per byte.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Read bytes back from hex written by `to_hex()`.
+fn from_hex(text: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if text.len() % 2 != 0 || !text.is_ascii() {
+        return Err("invalid clipboard line".into());
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|e| Box::new(e) as Box<dyn Error>))
+        .collect()
+}
@@ -34,6 +34,24 @@ pub struct ObjectConfig {
     pub category: String,
     pub image_speed: Option<i32>,
     pub default_b_box: Option<(i32, i32, i32, i32)>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub price: Option<i32>,
+    #[serde(default)]
+    pub layer: RenderLayer,
+    #[serde(default)]
+    pub placement: PlacementFlags,
+    // Lists after the placement table are left out when empty, as TOML can't have plain values after a table
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub b_box_overrides: Vec<BBoxOverride>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub surfaces: Vec<SurfaceConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub states: Vec<StateConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub animations: Vec<AnimationConfig>,
 }
 
 impl ObjectConfig {
@@ -47,8 +65,97 @@ impl ObjectConfig {
             category: String::new(),
             image_speed: None,
             default_b_box: None,
+            tags: Vec::new(),
+            display_name: None,
+            description: None,
+            price: None,
+            layer: RenderLayer::default(),
+            placement: PlacementFlags::default(),
+            b_box_overrides: Vec::new(),
+            surfaces: Vec::new(),
+            states: Vec::new(),
+            animations: Vec::new(),
         }
     }
+
+    /** Get the bounding box used by the given side and subimage. A box for that exact subimage wins over a
+     * box for the whole side, which wins over `default_b_box`.
+     */
+    pub fn b_box_for(&self, side: i32, subimage: i32) -> Option<(i32, i32, i32, i32)> {
+        self.get_b_box_override(side, Some(subimage))
+            .or_else(|| self.get_b_box_override(side, None))
+            .or(self.default_b_box)
+    }
+
+    /** Get the override for a side, or for one subimage of a side if `subimage` is given.
+     */
+    pub fn get_b_box_override(
+        &self,
+        side: i32,
+        subimage: Option<i32>,
+    ) -> Option<(i32, i32, i32, i32)> {
+        self.b_box_overrides
+            .iter()
+            .find(|o| o.side == side && o.subimage == subimage)
+            .map(|o| o.b_box)
+    }
+
+    /** Create or replace the override for a side, or for one subimage of a side.
+     */
+    pub fn set_b_box_override(
+        &mut self,
+        side: i32,
+        subimage: Option<i32>,
+        b_box: (i32, i32, i32, i32),
+    ) {
+        match self
+            .b_box_overrides
+            .iter_mut()
+            .find(|o| o.side == side && o.subimage == subimage)
+        {
+            Some(o) => o.b_box = b_box,
+            None => self.b_box_overrides.push(BBoxOverride {
+                side,
+                subimage,
+                b_box,
+            }),
+        }
+    }
+
+    /** Remove the override for a side, or for one subimage of a side.
+     */
+    pub fn remove_b_box_override(&mut self, side: i32, subimage: Option<i32>) {
+        self.b_box_overrides
+            .retain(|o| !(o.side == side && o.subimage == subimage));
+    }
+
+    /** Get the name shown to players, falling back to the type name if no display name is set.
+     */
+    pub fn get_display_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+
+    /** Find the animation clip with the given name, if the object type defines one.
+     */
+    pub fn get_animation(&self, name: &str) -> Option<&AnimationConfig> {
+        self.animations.iter().find(|a| a.name == name)
+    }
+
+    /** Find the interaction state with the given name, if the object type defines one.
+     */
+    pub fn get_state(&self, name: &str) -> Option<&StateConfig> {
+        self.states.iter().find(|s| s.name == name)
+    }
+
+    /** Get the state that interacting with an object in state `current` switches it to. States are
+     * cycled in the order they are listed, and an object without a known state goes to the first one.
+     */
+    pub fn next_state(&self, current: Option<&str>) -> Option<&StateConfig> {
+        let next = current
+            .and_then(|name| self.states.iter().position(|s| s.name == name))
+            .map_or(0, |i| (i + 1) % self.states.len());
+        self.states.get(next)
+    }
 }
 
 impl Default for ObjectConfig {
@@ -57,11 +164,237 @@ impl Default for ObjectConfig {
     }
 }
 
+/** Replaces the default bounding box for one side of an object type, or for a single subimage of that
+ * side when `subimage` is set.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BBoxOverride {
+    pub side: i32,
+    pub subimage: Option<i32>,
+    pub b_box: (i32, i32, i32, i32),
+}
+
+/** A named state that the player can switch an object into by interacting with it, such as a television
+ * being on or off. The state either shows a single subimage of the current side or plays an animation
+ * clip. If both are set the animation wins.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StateConfig {
+    pub name: String,
+    pub frame: Option<i32>,
+    pub animation: Option<String>,
+}
+
+/** A region of an object type's sprite that stackable objects can be set down on, such as a desk top.
+ * `rect` is relative to the top left of the untransformed sprite.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SurfaceConfig {
+    pub rect: (i32, i32, i32, i32),
+}
+
+/** Describes where an object type may go and what the player can do with it.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct PlacementFlags {
+    /// Stands on the floor.
+    pub floor: bool,
+    /// Hangs on a wall instead of standing on the floor.
+    pub wall_mounted: bool,
+    /// Can be set down on top of other objects.
+    pub stackable: bool,
+    /// Shows up in the furniture menu so the player can create it.
+    pub player_placeable: bool,
+    /// Can be picked up and moved with the mouse.
+    pub draggable: bool,
+}
+
+impl Default for PlacementFlags {
+    fn default() -> Self {
+        Self {
+            floor: true,
+            wall_mounted: false,
+            stackable: false,
+            player_placeable: true,
+            draggable: true,
+        }
+    }
+}
+
+/** Which layer an object type is drawn on. Layers are drawn bottom to top in the order listed here and
+ * objects are only sorted by depth against objects on the same layer.
+ */
+#[derive(
+    Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderLayer {
+    /// Flat on the ground, under everything else.
+    Floor,
+    /// Rugs and mats lying on top of the floor.
+    FloorDecal,
+    /// Furniture, walls and the player.
+    #[default]
+    Furniture,
+    /// Paintings, shelves and anything else hanging on a wall.
+    WallMounted,
+    /// Drawn over the whole room, such as lights or ceiling fixtures.
+    Overlay,
+}
+
+impl RenderLayer {
+    /// All layers, in drawing order.
+    pub const ALL: [RenderLayer; 5] = [
+        RenderLayer::Floor,
+        RenderLayer::FloorDecal,
+        RenderLayer::Furniture,
+        RenderLayer::WallMounted,
+        RenderLayer::Overlay,
+    ];
+
+    /** Check whether objects on the two layers block each other. Only furniture blocks furniture, and
+     * only wall-mounted objects block each other, so rugs can go under tables and paintings can hang
+     * over a desk.
+     */
+    pub fn collides_with(self, other: RenderLayer) -> bool {
+        self == other && matches!(self, RenderLayer::Furniture | RenderLayer::WallMounted)
+    }
+
+    /** Get the layer drawn after this one, wrapping back to the first layer.
+     */
+    pub fn next(self) -> RenderLayer {
+        let i = Self::ALL.iter().position(|&l| l == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+/** How an animation clip behaves once it has shown its last frame.
+ */
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimationMode {
+    /// Start over from the first frame.
+    #[default]
+    Loop,
+    /// Play the frames backwards, then forwards again, forever.
+    PingPong,
+    /// Stop on the last frame.
+    OneShot,
+}
+
+/** A named animation clip for an object type. `frames` are subimage indices of the current side and
+ * `durations` holds how many game frames each of them is shown for. If there are fewer durations than
+ * frames, the last duration is used for the rest of the clip.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnimationConfig {
+    pub name: String,
+    pub frames: Vec<i32>,
+    #[serde(default)]
+    pub durations: Vec<i32>,
+    #[serde(default)]
+    pub mode: AnimationMode,
+}
+
+impl AnimationConfig {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            frames: Vec::new(),
+            durations: Vec::new(),
+            mode: AnimationMode::Loop,
+        }
+    }
+
+    /** Get the amount of game frames that the clip frame at `pos` is displayed for. Never less than 1.
+     */
+    pub fn duration_of(&self, pos: usize) -> i32 {
+        self.durations
+            .get(pos)
+            .or_else(|| self.durations.last())
+            .copied()
+            .unwrap_or(1)
+            .max(1)
+    }
+}
+
+/** Per-frame alpha bitmask of a spritesheet, used for pixel-perfect hit testing. Frames are stored in
+ * spritesheet order (row by row of sides) and each frame packs one bit per pixel, row-major.
+ */
+pub struct SpriteMask {
+    pub frame_w: i32,
+    pub frame_h: i32,
+    pub img_per_side: i32,
+    frames: Vec<Vec<u64>>,
+}
+
+/// Pixels with an alpha at or below this count as transparent when building sprite masks.
+pub const MASK_ALPHA_THRESHOLD: u8 = 16;
+
+impl SpriteMask {
+    /** Build the mask of every frame of `img`, which is laid out as described by `conf`.
+     */
+    pub fn from_image(img: &Image, conf: &ObjectConfig) -> Self {
+        let (frame_w, frame_h) = conf.dim;
+        let colors = img.get_image_data();
+        let words = ((frame_w * frame_h) as usize).div_ceil(64);
+        let mut frames = Vec::new();
+
+        for side in 0..conf.sides {
+            for index in 0..conf.img_per_side {
+                let mut bits = vec![0u64; words];
+                for y in 0..frame_h {
+                    for x in 0..frame_w {
+                        let px = index * frame_w + x;
+                        let py = side * frame_h + y;
+                        if px >= img.width() || py >= img.height() {
+                            continue;
+                        }
+                        if colors[(py * img.width() + px) as usize].a > MASK_ALPHA_THRESHOLD {
+                            let bit = (y * frame_w + x) as usize;
+                            bits[bit / 64] |= 1u64 << (bit % 64);
+                        }
+                    }
+                }
+                frames.push(bits);
+            }
+        }
+
+        Self {
+            frame_w,
+            frame_h,
+            img_per_side: conf.img_per_side,
+            frames,
+        }
+    }
+
+    /** Check whether the pixel at (x, y) of the given frame is visible. Pixels outside of the frame
+     * never are.
+     */
+    pub fn is_opaque(&self, side: i32, index: i32, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x >= self.frame_w || y >= self.frame_h {
+            return false;
+        }
+        match self.frames.get((side * self.img_per_side + index) as usize) {
+            Some(bits) => {
+                let bit = (y * self.frame_w + x) as usize;
+                bits[bit / 64] & (1u64 << (bit % 64)) != 0
+            }
+            None => false,
+        }
+    }
+}
+
 lazy_static::lazy_static! {
     /** Hash Map of object types that are loaded into memory. Each map element contains an Arc with a texture and an ObjectConfig object. It is static so that all modules which import game can use it.
      */
     pub static ref LOADED_TEXTURES: Mutex<HashMap<i32, Arc<(Texture2D, ObjectConfig)>>> = Mutex::new(HashMap::new());
 
+    /** Hash Map of the alpha masks of each loaded object type's spritesheet, keyed by object type ID.
+     */
+    pub static ref SPRITE_MASKS: Mutex<HashMap<i32, Arc<SpriteMask>>> = Mutex::new(HashMap::new());
+
     /** A static boolean used to tell if the game textures and data have been loaded yet.
      */
     static ref INITIALIZED: atomic::AtomicBool = atomic::AtomicBool::new(false);
@@ -94,6 +427,11 @@ pub fn init(rl: &mut RaylibHandle, rt: &RaylibThread) {
         let obj: ObjectConfig =
             toml::from_str(confstr.as_str()).expect("Unable to parse TOML Object configuration!");
 
+        SPRITE_MASKS
+            .lock()
+            .expect("Unable to lock SPRITE_MASKS mutex!")
+            .insert(obj.id, Arc::new(SpriteMask::from_image(&img, &obj)));
+
         LOADED_TEXTURES
             .lock()
             .expect("Unable to lock LOADED_TEXTURES mutex!")
@@ -136,6 +474,10 @@ pub fn destroy() {
         .lock()
         .expect("Unable to lock LOADED_TEXTURES mutex!")
         .clear();
+    SPRITE_MASKS
+        .lock()
+        .expect("Unable to lock SPRITE_MASKS mutex!")
+        .clear();
 
     INITIALIZED.store(false, atomic::Ordering::Relaxed);
 }
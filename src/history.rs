@@ -0,0 +1,347 @@
+//! # History
+//! `history` keeps track of edits made to the objects of a room so that they can be undone and redone.
+//! Edits are stored as commands holding save snapshots of the objects from before and after the edit.
+
+use crate::object::*;
+use crate::save::*;
+use crate::spatial::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::rc::Rc;
+
+/// Number of edits that can be undone when no other limit is asked for.
+pub const DEFAULT_LIMIT: usize = 100;
+
+/** A single edit to the objects of a room. Objects are stored as save snapshots, and found again by ID.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// An object was put into the room.
+    Add(Vec<u8>),
+    /// An object was taken out of the room.
+    Remove(Vec<u8>),
+    /// An object was changed.
+    Modify {
+        id: i32,
+        before: Vec<u8>,
+        after: Vec<u8>,
+    },
+    /// Several edits that are undone and redone together.
+    Batch(Vec<Command>),
+}
+
+impl Command {
+    /** Get the command that reverts this one.
+     */
+    pub fn inverse(&self) -> Command {
+        match self {
+            Command::Add(bytes) => Command::Remove(bytes.clone()),
+            Command::Remove(bytes) => Command::Add(bytes.clone()),
+            Command::Modify { id, before, after } => Command::Modify {
+                id: *id,
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Command::Batch(cmds) => {
+                Command::Batch(cmds.iter().rev().map(|c| c.inverse()).collect())
+            }
+        }
+    }
+
+    /** Carry out the command on the objects of a room, keeping the grid up to date. If part of a batch
+     * fails, the parts already carried out are reverted so the room is left as it was.
+     */
+    pub fn apply(
+        &self,
+        objects: &mut Vec<GenObj>,
+        grid: &mut SpatialGrid,
+    ) -> Result<(), Box<dyn Error>> {
+        match self {
+            Command::Add(bytes) => {
+                let obj = Rc::new(RefCell::new(GenericObject::from_bytes(bytes)?.0));
+                grid.insert(&obj);
+                objects.push(obj);
+            }
+            Command::Remove(bytes) => {
+                let id = GenericObject::from_bytes(bytes)?.0.get_id();
+                let obj = find_object(objects, id)?;
+                grid.remove(&obj);
+                objects.retain(|o| !Rc::ptr_eq(o, &obj));
+            }
+            Command::Modify { id, after, .. } => {
+                let obj = find_object(objects, *id)?;
+                // Replace the contents so that anything holding on to the object sees the change
+                *obj.borrow_mut() = GenericObject::from_bytes(after)?.0;
+                grid.update(&obj);
+            }
+            Command::Batch(cmds) => {
+                for (i, cmd) in cmds.iter().enumerate() {
+                    if let Err(e) = cmd.apply(objects, grid) {
+                        for done in cmds[..i].iter().rev() {
+                            if let Err(e) = done.inverse().apply(objects, grid) {
+                                println!("Unable to roll back an edit: {}", e);
+                            }
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Find an object of a room by its ID.
+fn find_object(objects: &[GenObj], id: i32) -> Result<GenObj, Box<dyn Error>> {
+    objects
+        .iter()
+        .find(|o| o.borrow().get_id() == id)
+        .cloned()
+        .ok_or_else(|| format!("no object with id {} to undo", id).into())
+}
+
+/** Undo and redo stacks for the edits made to a room. Only the last `limit` edits are kept.
+ *
+ * Edits that take a while, like carrying or dragging an object around, are opened with `begin()` and
+ * recorded as one command once they are finished with `commit()`.
+ */
+pub struct History {
+    undo: VecDeque<Command>,
+    redo: Vec<Command>,
+    limit: usize,
+    open: HashMap<i32, Option<Vec<u8>>>,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit,
+            open: HashMap::new(),
+        }
+    }
+
+    /** Record a command that has already been carried out. Anything that could be redone is forgotten.
+     */
+    pub fn push(&mut self, cmd: Command) {
+        if let Command::Batch(cmds) = &cmd {
+            if cmds.is_empty() {
+                return;
+            }
+        }
+        self.redo.clear();
+        self.undo.push_back(cmd);
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+
+    /** Record several commands that have already been carried out as one edit.
+     */
+    pub fn push_all(&mut self, mut cmds: Vec<Command>) {
+        match cmds.len() {
+            0 => (),
+            1 => self.push(cmds.remove(0)),
+            _ => self.push(Command::Batch(cmds)),
+        }
+    }
+
+    /** Change an object and record the change. Nothing is recorded if the object ends up the same.
+     */
+    pub fn modify<F>(&mut self, obj: &GenObj, edit: F)
+    where
+        F: FnOnce(&mut GenericObject),
+    {
+        let before = obj.borrow().to_bytes();
+        edit(&mut obj.borrow_mut());
+        let after = obj.borrow().to_bytes();
+        if before != after {
+            let id = obj.borrow().get_id();
+            self.push(Command::Modify { id, before, after });
+        }
+    }
+
+    /** Change several objects in the same way and record it as one edit.
+     */
+    pub fn modify_all<F>(&mut self, objs: &[GenObj], mut edit: F)
+    where
+        F: FnMut(&mut GenericObject),
+    {
+        let cmds = objs
+            .iter()
+            .filter_map(|obj| {
+                let before = obj.borrow().to_bytes();
+                edit(&mut obj.borrow_mut());
+                let after = obj.borrow().to_bytes();
+                let id = obj.borrow().get_id();
+                if before != after {
+                    Some(Command::Modify { id, before, after })
+                } else {
+                    None
+                }
+            })
+            .collect();
+        self.push_all(cmds);
+    }
+
+    /** Start an edit of an object that will be recorded by `commit()`. Does nothing if the object is
+     * already being edited.
+     */
+    pub fn begin(&mut self, obj: &GenObj) {
+        let id = obj.borrow().get_id();
+        self.open
+            .entry(id)
+            .or_insert_with(|| Some(obj.borrow().to_bytes()));
+    }
+
+    /** Start an edit of an object that has just been made. It is recorded as added to the room by
+     * `commit()`.
+     */
+    pub fn begin_new(&mut self, obj: &GenObj) {
+        self.open.insert(obj.borrow().get_id(), None);
+    }
+
+    /** Whether an object is being edited.
+     */
+    pub fn is_open(&self, obj: &GenObj) -> bool {
+        self.open.contains_key(&obj.borrow().get_id())
+    }
+
+    /** Finish an edit started with `begin()` or `begin_new()` and record it.
+     */
+    pub fn commit(&mut self, obj: &GenObj) {
+        if let Some(cmd) = self.take_commit(obj) {
+            self.push(cmd);
+        }
+    }
+
+    /** Finish the edits of several objects and record them as one edit.
+     */
+    pub fn commit_all(&mut self, objs: &[GenObj]) {
+        let cmds = objs
+            .iter()
+            .filter_map(|obj| self.take_commit(obj))
+            .collect();
+        self.push_all(cmds);
+    }
+
+    /// Close the edit of an object, getting the command that records it if anything changed.
+    fn take_commit(&mut self, obj: &GenObj) -> Option<Command> {
+        let id = obj.borrow().get_id();
+        let after = obj.borrow().to_bytes();
+        match self.open.remove(&id) {
+            Some(Some(before)) if before != after => Some(Command::Modify { id, before, after }),
+            Some(None) => Some(Command::Add(after)),
+            _ => None,
+        }
+    }
+
+    /** Record an object being taken out of the room. An object that is being edited is recorded as it was
+     * before the edit, and one that was only just made isn't recorded at all.
+     */
+    pub fn remove(&mut self, obj: &GenObj) {
+        if let Some(cmd) = self.take_remove(obj) {
+            self.push(cmd);
+        }
+    }
+
+    /** Record several objects being put into the room as one edit.
+     */
+    pub fn add_all(&mut self, objs: &[GenObj]) {
+        let cmds = objs
+            .iter()
+            .map(|obj| Command::Add(obj.borrow().to_bytes()))
+            .collect();
+        self.push_all(cmds);
+    }
+
+    /** Record several objects being taken out of the room as one edit.
+     */
+    pub fn remove_all(&mut self, objs: &[GenObj]) {
+        let cmds = objs
+            .iter()
+            .filter_map(|obj| self.take_remove(obj))
+            .collect();
+        self.push_all(cmds);
+    }
+
+    /// Get the command that records an object being taken out of the room, closing any edit of it.
+    fn take_remove(&mut self, obj: &GenObj) -> Option<Command> {
+        let id = obj.borrow().get_id();
+        match self.open.remove(&id) {
+            Some(Some(before)) => Some(Command::Remove(before)),
+            Some(None) => None,
+            None => Some(Command::Remove(obj.borrow().to_bytes())),
+        }
+    }
+
+    /** Revert the last edit. Returns false if there was nothing to undo. An edit that can't be reverted
+     * is kept, so nothing is lost.
+     */
+    pub fn undo(
+        &mut self,
+        objects: &mut Vec<GenObj>,
+        grid: &mut SpatialGrid,
+    ) -> Result<bool, Box<dyn Error>> {
+        match self.undo.pop_back() {
+            Some(cmd) => match cmd.inverse().apply(objects, grid) {
+                Ok(()) => {
+                    self.redo.push(cmd);
+                    Ok(true)
+                }
+                Err(e) => {
+                    self.undo.push_back(cmd);
+                    Err(e)
+                }
+            },
+            None => Ok(false),
+        }
+    }
+
+    /** Carry out the last undone edit again. Returns false if there was nothing to redo. An edit that
+     * can't be carried out is kept, so nothing is lost.
+     */
+    pub fn redo(
+        &mut self,
+        objects: &mut Vec<GenObj>,
+        grid: &mut SpatialGrid,
+    ) -> Result<bool, Box<dyn Error>> {
+        match self.redo.pop() {
+            Some(cmd) => match cmd.apply(objects, grid) {
+                Ok(()) => {
+                    self.undo.push_back(cmd);
+                    Ok(true)
+                }
+                Err(e) => {
+                    self.redo.push(cmd);
+                    Err(e)
+                }
+            },
+            None => Ok(false),
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /** Forget every edit, including ones that haven't been committed yet.
+     */
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.open.clear();
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_LIMIT)
+    }
+}
@@ -0,0 +1,277 @@
+//! # House
+//! `house` joins several rooms into one house. Only one room is played at a time; the objects of the
+//! other rooms are kept here until Naomi walks back in through a door.
+
+use crate::game::{mutex_get, BASE_COLOR_DISABLED, BASE_COLOR_FOCUSED, BORDER_COLOR_NORMAL};
+use crate::object::*;
+use crate::room::*;
+use crate::save::*;
+use crate::util::{draw_text_centered, ds_rounded_rectangle};
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs;
+use std::rc::Rc;
+
+/** Layout of a house as read from its TOML file. `rooms` holds the paths of the room layouts and
+ * `start` the name of the room a new game starts in.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HouseConfig {
+    pub name: String,
+    pub start: String,
+    pub rooms: Vec<String>,
+}
+
+/** A set of rooms joined by doors, along with the objects placed in each of them. The objects of the
+ * current room are handed out with `take_objects()` while it is being played, so that the main loop can
+ * own them.
+ */
+pub struct House {
+    pub name: String,
+    pub rooms: Vec<Room>,
+    pub objects: Vec<Vec<GenObj>>,
+    pub current: usize,
+}
+
+impl House {
+    /** Read a house and all of its rooms from disk. Walls are generated for every room, taking their
+     * IDs from `next_id`.
+     */
+    pub fn load(path: &str, next_id: &mut i32) -> Result<Self, Box<dyn Error>> {
+        let confstr = fs::read_to_string(path)?;
+        let conf: HouseConfig = toml::from_str(confstr.as_str())?;
+
+        let mut rooms = Vec::new();
+        for room_path in conf.rooms.iter() {
+            rooms.push(Room::load(room_path)?);
+        }
+        let current = rooms
+            .iter()
+            .position(|r| r.name == conf.start)
+            .ok_or("start room is not part of the house")?;
+        let objects = rooms
+            .iter()
+            .map(|r| {
+                r.generate_walls(next_id)
+                    .into_iter()
+                    .map(|o| Rc::new(RefCell::new(o)))
+                    .collect()
+            })
+            .collect();
+
+        Ok(Self {
+            name: conf.name,
+            rooms,
+            objects,
+            current,
+        })
+    }
+
+    /** Get the room that is being played.
+     */
+    pub fn room(&self) -> &Room {
+        &self.rooms[self.current]
+    }
+
+    pub fn room_mut(&mut self) -> &mut Room {
+        &mut self.rooms[self.current]
+    }
+
+    /** Find a room by its name.
+     */
+    pub fn find_room(&self, name: &str) -> Option<usize> {
+        self.rooms.iter().position(|r| r.name == name)
+    }
+
+    /** Find a door of the current room overlapping `rect` that leads to another room of the house.
+     */
+    pub fn door_at(&self, rect: Rectangle) -> Option<usize> {
+        self.room()
+            .door_at(rect)
+            .filter(|&d| self.door_target(d).is_some())
+    }
+
+    /** Get the index of the room a door of the current room leads to.
+     */
+    pub fn door_target(&self, door: usize) -> Option<usize> {
+        self.room()
+            .doors
+            .get(door)
+            .and_then(|d| d.target.as_deref())
+            .and_then(|name| self.find_room(name))
+    }
+
+    /** Take the objects of the current room out of the house.
+     */
+    pub fn take_objects(&mut self) -> Vec<GenObj> {
+        std::mem::take(&mut self.objects[self.current])
+    }
+
+    /** Put the objects of the current room back into the house.
+     */
+    pub fn store_objects(&mut self, objects: Vec<GenObj>) {
+        self.objects[self.current] = objects;
+    }
+
+    /** Leave the current room through one of its doors. The objects of the current room are stored and
+     * those of the room the door leads to are returned. If the door doesn't lead to a room in this house
+     * nothing changes and the objects are handed back as the error.
+     */
+    pub fn go_through(
+        &mut self,
+        door: usize,
+        objects: Vec<GenObj>,
+    ) -> Result<Vec<GenObj>, Vec<GenObj>> {
+        match self.door_target(door) {
+            Some(target) => {
+                self.store_objects(objects);
+                self.current = target;
+                Ok(self.take_objects())
+            }
+            None => Err(objects),
+        }
+    }
+
+    /** Get the largest object ID used in any room, or -1 if there are no objects. `current` holds the
+     * objects of the current room if they have been taken out.
+     */
+    pub fn max_id(&self, current: &[GenObj]) -> i32 {
+        self.objects
+            .iter()
+            .flatten()
+            .chain(current.iter())
+            .map(|o| o.borrow().get_id())
+            .max()
+            .unwrap_or(-1)
+    }
+
+    /** Draw an overview map of every room in the house inside `rec`, using each room's `map_pos`.
+     * The room being played is highlighted and doors are drawn as gaps in the walls.
+     */
+    pub fn draw_map(&self, rd: &mut RaylibDrawHandle, font: &Font, rec: Rectangle) {
+        ds_rounded_rectangle(rd, rec, 0.1, 4);
+        if self.rooms.is_empty() {
+            return;
+        }
+
+        // Fit the bounds of all rooms into the map with a margin
+        let min_x = self.rooms.iter().map(|r| r.map_pos.0).min().unwrap_or(0);
+        let min_y = self.rooms.iter().map(|r| r.map_pos.1).min().unwrap_or(0);
+        let max_x = house
+            .rooms
+            .iter()
+            .map(|r| r.map_pos.0 + r.width())
+            .max()
+            .unwrap_or(1);
+        let max_y = house
+            .rooms
+            .iter()
+            .map(|r| r.map_pos.1 + r.height())
+            .max()
+            .unwrap_or(1);
+        let margin = 16.0;
+        let scale = ((rec.width - 2.0 * margin) / (max_x - min_x).max(1) as f32)
+            .min((rec.height - 2.0 * margin) / (max_y - min_y).max(1) as f32);
+        let to_map = |x: i32, y: i32| {
+            rvec2(
+                rec.x + margin + (x - min_x) as f32 * scale,
+                rec.y + margin + (y - min_y) as f32 * scale,
+            )
+        };
+        let border_color = mutex_get(&BORDER_COLOR_NORMAL);
+
+        for (i, room) in self.rooms.iter().enumerate() {
+            let corner = to_map(room.map_pos.0, room.map_pos.1);
+            let room_rect = rrect(
+                corner.x,
+                corner.y,
+                room.width() as f32 * scale,
+                room.height() as f32 * scale,
+            );
+            let fill = if i == self.current {
+                mutex_get(&BASE_COLOR_FOCUSED)
+            } else {
+                mutex_get(&BASE_COLOR_DISABLED)
+            };
+            rd.draw_rectangle_rec(room_rect, fill);
+
+            for seg in room.walls.iter() {
+                rd.draw_line_ex(
+                    to_map(room.map_pos.0 + seg.from.0, room.map_pos.1 + seg.from.1),
+                    to_map(room.map_pos.0 + seg.to.0, room.map_pos.1 + seg.to.1),
+                    2.0,
+                    border_color,
+                );
+            }
+            for door in 0..room.doors.len() {
+                if let Some(d) = room.door_rect(door) {
+                    let top_left = to_map(room.map_pos.0 + d.x as i32, room.map_pos.1 + d.y as i32);
+                    rd.draw_rectangle_rec(
+                        rrect(top_left.x, top_left.y, d.width * scale, d.height * scale),
+                        fill,
+                    );
+                }
+            }
+
+            draw_text_centered(
+                rd,
+                font,
+                room.name.as_str(),
+                (room_rect.x + room_rect.width / 2.0) as i32,
+                (room_rect.y + room_rect.height / 2.0) as i32,
+                16,
+                Color::BLACK,
+            );
+        }
+    }
+}
+
+/* SAVE IMPLEMENTATIONS */
+impl Saveable<Self> for House {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = self.name.to_bytes();
+        result.extend(self.current.to_bytes());
+        result.extend(self.rooms.to_bytes());
+        result.extend(self.objects.to_bytes());
+        result
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn Error>> {
+        let mut bytes_read = 0;
+        let name = {
+            let base = String::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let current = {
+            let base = usize::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let rooms = {
+            let base = Vec::<Room>::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let objects = {
+            let base = Vec::<Vec<GenObj>>::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        if current >= rooms.len() || objects.len() != rooms.len() {
+            return Err("invalid house read".into());
+        }
+
+        Ok(SaveInfo(
+            House {
+                name,
+                rooms,
+                objects,
+                current,
+            },
+            bytes_read,
+        ))
+    }
+}
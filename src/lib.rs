@@ -14,3 +14,36 @@ pub mod util;
 
 /// Module to define how saving to a file works
 pub mod save;
+
+/// Module to bucket objects on a grid for fast collision and picking queries
+pub mod spatial;
+
+/// Module to describe room layouts and generate their walls
+pub mod room;
+
+/// Module to join rooms into a house and keep track of the objects in each of them
+pub mod house;
+
+/// Module to scroll and zoom the view of a room
+pub mod camera;
+
+/// Module to fit the game onto the window and convert between window and room positions
+pub mod viewport;
+
+/// Module to record edits to a room so that they can be undone and redone
+pub mod history;
+
+/// Module to select several objects and edit them together
+pub mod selection;
+
+/// Module to copy and paste objects as text through the system clipboard
+pub mod clipboard;
+
+/// Module to save groups of objects as prefabs and place them again
+pub mod prefab;
+
+/// Module to line objects up with a grid and with each other when they are set down
+pub mod snap;
+
+/// Module to keep recently used colours and saved palettes for tinting objects
+pub mod palette;
@@ -1,9 +1,19 @@
+use crate::game::RenderLayer;
+use crate::history::*;
 use crate::object::*;
+use crate::room::Room;
 use crate::save::*;
+use crate::snap::*;
+use crate::spatial::*;
 use raylib::prelude::*;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+/// How far Naomi walks with each key press, in pixels. Her walk cycle and the spot she checks before
+/// stepping (`facing_rect()`) are made for this distance, so she keeps to it whatever the snap grid is
+/// set to. The snap grid only lines up objects.
+pub const STEP_SIZE: i32 = 16;
+
 #[derive(Clone, PartialEq)]
 pub enum Direction {
     Right,
@@ -25,9 +35,6 @@ pub fn dir_to_i32(dir: &Direction) -> i32 {
     dir_to_u32(dir) as i32
 }
 
-/// Type alias because me is lazy
-type GenObj = Rc<RefCell<GenericObject>>;
-
 
 /** Main player for the game. Has additional methods compared to basic objects to allow for control
  * of the game state.
@@ -87,7 +94,7 @@ impl Object for Naomi {
                     }
                 }
             }
-            if self.base.pos_aligned(16) {
+            if self.base.pos_aligned(STEP_SIZE) {
                 self.moving = false;
                 self.base.set_shift(0);
             }
@@ -118,6 +125,14 @@ impl Object for Naomi {
     fn get_collision_rect(&self) -> Rectangle {
         self.base.get_collision_rect()
     }
+
+    fn contains_point(&self, point: Vector2) -> bool {
+        self.base.contains_point(point)
+    }
+
+    fn get_layer(&self) -> RenderLayer {
+        self.base.get_layer()
+    }
 }
 
 impl Naomi {
@@ -136,25 +151,35 @@ impl Naomi {
         result.base.set_shift(0);
         result
     }
+    /// Play one of Naomi's animation clips unless it is already playing. Returns false if Naomi's
+    /// obj.toml doesn't define the clip.
+    fn play_clip(&mut self, name: &str) -> bool {
+        if self.base.current_animation() == Some(name) {
+            return true;
+        }
+        self.base.play_animation(name)
+    }
     pub fn get_scrw(&self) -> i32 {
         self.scrw
     }
     pub fn get_scrh(&self) -> i32 {
         self.scrh
     }
-    pub fn is_spot_free(
-        &self,
-        spot: Rectangle,
-        objects: &[GenObj],
-    ) -> bool {
-        if self.ghost { return true; }
+    pub fn is_spot_free(&self, spot: Rectangle, grid: &SpatialGrid) -> bool {
+        if self.ghost {
+            return true;
+        }
 
-        for obj in objects.iter() {
+        for obj in grid.query_rect(spot).iter() {
             if let Some(o) = &self.select_obj {
                 if *o.borrow() == *obj.borrow(){
                     continue;
                 }
             }
+            // Rugs, paintings and the like don't get in Naomi's way
+            if !self.get_layer().collides_with(obj.borrow().get_layer()) {
+                continue;
+            }
             if obj.borrow().collide(Some(&spot)){
                 return false;
             }
@@ -163,6 +188,68 @@ impl Naomi {
         true
     }
     
+    /** Check whether the object Naomi carries can be set down where it is: on the floor or a wall as its
+     * placement flags allow, or on the surface `surface`, and not on top of anything it can't share
+     * space with.
+     */
+    pub fn can_set_down(
+        &self,
+        obj: &GenObj,
+        surface: Option<&GenObj>,
+        grid: &SpatialGrid,
+        room: &Room,
+    ) -> bool {
+        let ignore: Vec<GenObj> = surface.into_iter().cloned().collect();
+        room.allows_placement(&obj.borrow(), surface.is_some()) && !grid.is_blocked(obj, &ignore)
+    }
+
+    /// Get the spot Naomi's bounding box would take up after one step in the direction she faces.
+    pub fn facing_rect(&self) -> Rectangle {
+        let step = STEP_SIZE as f32;
+        let (x_off, y_off) = match self.dir {
+            Direction::Right => (step, 0.),
+            Direction::Up => (0., -step),
+            Direction::Left => (-step, 0.),
+            Direction::Down => (0., step),
+        };
+        self.base
+            .b_box
+            .map(|r| {
+                rrect(
+                    r.x + x_off + self.base.pos.x as f32,
+                    r.y + y_off + self.base.pos.y as f32,
+                    r.width,
+                    r.height,
+                )
+            })
+            .unwrap()
+    }
+
+    /// Interact with the topmost interactive object in front of Naomi, ignoring the one she carries.
+    /// The change is recorded in `history`. Returns true if there was something to interact with.
+    pub fn interact(&self, grid: &mut SpatialGrid, history: &mut History) -> bool {
+        let target = grid
+            .query_rect(self.facing_rect())
+            .into_iter()
+            .filter(|obj| {
+                let is_carried = self
+                    .select_obj
+                    .as_ref()
+                    .map_or(false, |o| Rc::ptr_eq(o, obj));
+                !is_carried && obj.borrow().is_interactive()
+            })
+            .max_by_key(|obj| obj.borrow().get_draw_order());
+        match target {
+            Some(obj) => {
+                let mut changed = false;
+                history.modify(&obj, |o| changed = o.interact());
+                grid.update(&obj); // A new state can show a frame of a different shape
+                changed
+            }
+            None => false,
+        }
+    }
+
     /// Takes in an object and sets its position correctly.
     pub fn grab_object(
         &self,
@@ -179,99 +266,49 @@ impl Naomi {
         obj.pos = obj_position;
     }
 
-    /** Check for recent input from the user
+    /** Check for recent input from the user. Changes to the object Naomi carries are recorded in
+     * `history` as one edit once she sets it down, and it is lined up following `snap`. It is only set
+     * down where `room` allows it.
      */
     pub fn handle_input(
         &mut self,
         rl: &mut RaylibHandle,
         next_id: &mut i32,
         objects: &mut Vec<GenObj>,
+        grid: &mut SpatialGrid,
+        history: &mut History,
+        snap: &Snap,
+        room: &Room,
     ) -> Option<GenObj> {
         if self.moving {
             return None;
         }
 
+        if let Some(o) = &self.select_obj {
+            history.begin(o);
+        }
+
         let old_dir = self.dir.clone();
         let is_change_dir = rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
             || rl.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
         if rl.is_key_down(KeyboardKey::KEY_RIGHT) {
             self.dir = Direction::Right;
-            if !is_change_dir
-                && self.is_spot_free(
-                    self.base
-                        .b_box
-                        .map(|r| {
-                            rrect(
-                                r.x + 16. + self.base.pos.x as f32,
-                                r.y + self.base.pos.y as f32,
-                                r.width,
-                                r.height,
-                            )
-                        })
-                        .unwrap(),
-                    objects,
-                )
-            {
+            if !is_change_dir && self.is_spot_free(self.facing_rect(), grid) {
                 self.moving = true;
             }
         } else if rl.is_key_down(KeyboardKey::KEY_LEFT) {
             self.dir = Direction::Left;
-            if !is_change_dir
-                && self.is_spot_free(
-                    self.base
-                        .b_box
-                        .map(|r| {
-                            rrect(
-                                r.x - 16. + self.base.pos.x as f32,
-                                r.y + self.base.pos.y as f32,
-                                r.width,
-                                r.height,
-                            )
-                        })
-                        .unwrap(),
-                    objects,
-                )
-            {
+            if !is_change_dir && self.is_spot_free(self.facing_rect(), grid) {
                 self.moving = true;
             }
         } else if rl.is_key_down(KeyboardKey::KEY_DOWN) {
             self.dir = Direction::Down;
-            if !is_change_dir
-                && self.is_spot_free(
-                    self.base
-                        .b_box
-                        .map(|r| {
-                            rrect(
-                                r.x + self.base.pos.x as f32,
-                                r.y + 16. + self.base.pos.y as f32,
-                                r.width,
-                                r.height,
-                            )
-                        })
-                        .unwrap(),
-                    objects,
-                )
-            {
+            if !is_change_dir && self.is_spot_free(self.facing_rect(), grid) {
                 self.moving = true;
             }
         } else if rl.is_key_down(KeyboardKey::KEY_UP) {
             self.dir = Direction::Up;
-            if !is_change_dir
-                && self.is_spot_free(
-                    self.base
-                        .b_box
-                        .map(|r| {
-                            rrect(
-                                r.x + self.base.pos.x as f32,
-                                r.y - 16. + self.base.pos.y as f32,
-                                r.width,
-                                r.height,
-                            )
-                        })
-                        .unwrap(),
-                    objects,
-                )
-            {
+            if !is_change_dir && self.is_spot_free(self.facing_rect(), grid) {
                 self.moving = true;
             }
         }
@@ -296,12 +333,33 @@ impl Naomi {
                 Some(KeyboardKey::KEY_D) => {
                     o.borrow_mut().inc_side();
                 }
+                Some(KeyboardKey::KEY_H) => {
+                    o.borrow_mut().flip_horizontal();
+                }
+                Some(KeyboardKey::KEY_V) => {
+                    o.borrow_mut().flip_vertical();
+                }
+                Some(KeyboardKey::KEY_R) => {
+                    o.borrow_mut().rotate_cw();
+                    self.grab_object(o.clone());
+                }
+                Some(KeyboardKey::KEY_EQUAL) => {
+                    let scale = o.borrow().scale;
+                    o.borrow_mut().set_scale(scale + 1);
+                    self.grab_object(o.clone());
+                }
+                Some(KeyboardKey::KEY_MINUS) => {
+                    let scale = o.borrow().scale;
+                    o.borrow_mut().set_scale(scale - 1);
+                    self.grab_object(o.clone());
+                }
                 _ => (),
             };
 
             if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
                 let obj = o.clone();
                 self.select_obj = None;
+                history.remove(&obj);
                 return Some(obj);
             }
             
@@ -316,13 +374,21 @@ impl Naomi {
         }
 
         if self.moving {
-            let index = self.base.get_index();
-            if index == 1 || index == 2 {
-                self.base.set_index(2);
+            if !self.play_clip("walk") {
+                let index = self.base.get_index();
+                if index == 1 || index == 2 {
+                    self.base.set_index(2);
+                }
+                self.base.set_shift(self.base.get_default_shift());
             }
-            self.base.set_shift(self.base.get_default_shift());
+        } else {
+            self.play_clip("idle");
         }
         
+        if rl.is_key_pressed(KeyboardKey::KEY_F) {
+            self.interact(grid, history);
+        }
+
         if rl.is_key_pressed(KeyboardKey::KEY_G) {
             self.ghost = !self.ghost;
             if self.ghost {
@@ -333,8 +399,17 @@ impl Naomi {
         }
 
         if self.select_obj_type != 0 && rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
-            if self.select_obj.is_some() {
-                self.select_obj = None;
+            if let Some(obj) = self.select_obj.clone() {
+                snap.snap_object(&obj, grid, &[obj.clone()]);
+                // Set the object down on any surface in front of Naomi, if it can go there
+                let surface = grid.surface_under(&obj);
+                if self.can_set_down(&obj, surface.as_ref(), grid, room) {
+                    if let Some(parent) = surface {
+                        obj.borrow_mut().attach_to(&parent.borrow());
+                    }
+                    self.select_obj = None;
+                    history.commit(&obj);
+                }
             } else {
                 let mut obj = GenericObject::new(*next_id, self.select_obj_type, None);
                 *next_id += 1;
@@ -344,7 +419,9 @@ impl Naomi {
 
                 self.grab_object(obj.clone());
                 self.select_obj = Some(obj.clone());
+                history.begin_new(&obj);
 
+                grid.insert(&obj);
                 objects.push(obj);
             }
         }
@@ -383,9 +460,29 @@ impl Saveable<Self> for Naomi {
         result
     }
     fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn std::error::Error>> {
+        Self::read_with(bytes, GenericObject::from_bytes)
+    }
+}
+
+/// Reads one object record out of a save file.
+type ObjectReader = fn(&[u8]) -> Result<SaveInfo<GenericObject>, Box<dyn std::error::Error>>;
+
+impl Naomi {
+    /** Read Naomi from a save file without a header, which has the short object records.
+     */
+    pub fn from_legacy_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn std::error::Error>> {
+        Self::read_with(bytes, GenericObject::from_legacy_bytes)
+    }
+
+    /** Read Naomi, using `read_obj` for her own object and the object she is carrying.
+     */
+    fn read_with(
+        bytes: &[u8],
+        read_obj: ObjectReader,
+    ) -> Result<SaveInfo<Self>, Box<dyn std::error::Error>> {
         let mut bytes_read = 0;
         let base = {
-            let base = GenericObject::from_bytes(&bytes[bytes_read..])?;
+            let base = read_obj(&bytes[bytes_read..])?;
             bytes_read += base.1;
             base.0
         };
@@ -410,9 +507,16 @@ impl Saveable<Self> for Naomi {
             base.0
         };
         let select_obj = {
-            let base = Option::<GenObj>::from_bytes(&bytes[bytes_read..])?;
-            bytes_read += base.1;
-            base.0
+            // Laid out like an Option<GenObj>
+            let is_some = bool::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += is_some.1;
+            if is_some.0 {
+                let base = read_obj(&bytes[bytes_read..])?;
+                bytes_read += base.1;
+                Some(Rc::new(RefCell::new(base.0)))
+            } else {
+                None
+            }
         };
         let scrw = {
             let base = i32::from_bytes(&bytes[bytes_read..])?;
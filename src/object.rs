@@ -3,9 +3,16 @@
 use crate::game::*;
 use crate::save::*;
 use raylib::prelude::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
+/// Shared handle to an object. The room, the spatial grid, the history and Naomi all hold on to the
+/// same objects through these.
+pub type GenObj = Rc<RefCell<GenericObject>>;
+
 /** Simple struct to hold the position in screenspace of an object
  */
 #[derive(Copy, Clone)]
@@ -52,6 +59,87 @@ impl fmt::Display for Position {
     }
 }
 
+/** Playback state of an animation clip. Holds no reference to the clip itself so that it can be
+ * stepped against any `AnimationConfig`, which lets the object creator preview clips that aren't loaded.
+ */
+#[derive(Clone, Debug)]
+pub struct AnimationState {
+    pub name: String,
+    pub position: usize,
+    pub timer: i32,
+    pub reverse: bool,
+    pub finished: bool,
+}
+
+impl AnimationState {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            position: 0,
+            timer: 0,
+            reverse: false,
+            finished: false,
+        }
+    }
+
+    /** Get the subimage the clip is currently showing, or None if the clip has no frames.
+     */
+    pub fn current_frame(&self, clip: &AnimationConfig) -> Option<i32> {
+        clip.frames.get(self.position).copied()
+    }
+
+    /** Advance the clip by one game frame. Returns true when the clip reached the end of a cycle, which
+     * for one-shot clips means it has finished.
+     */
+    pub fn step(&mut self, clip: &AnimationConfig) -> bool {
+        if self.finished || clip.frames.is_empty() {
+            return self.finished;
+        }
+
+        self.timer += 1;
+        if self.timer < clip.duration_of(self.position) {
+            return false;
+        }
+        self.timer = 0;
+
+        let last = clip.frames.len() - 1;
+        match clip.mode {
+            AnimationMode::Loop => {
+                if self.position >= last {
+                    self.position = 0;
+                    return true;
+                }
+                self.position += 1;
+            }
+            AnimationMode::PingPong => {
+                if last == 0 {
+                    return true;
+                }
+                if self.reverse {
+                    self.position -= 1;
+                    if self.position == 0 {
+                        self.reverse = false;
+                        return true;
+                    }
+                } else {
+                    self.position += 1;
+                    if self.position >= last {
+                        self.reverse = true;
+                    }
+                }
+            }
+            AnimationMode::OneShot => {
+                if self.position >= last {
+                    self.finished = true;
+                    return true;
+                }
+                self.position += 1;
+            }
+        }
+        false
+    }
+}
+
 /** This trait defines what methods all objects are expected to implement.
  */
 pub trait Object {
@@ -63,6 +151,15 @@ pub trait Object {
     fn get_depth(&self) -> i32;
     fn get_id(&self) -> i32;
     fn get_collision_rect(&self) -> Rectangle;
+    fn contains_point(&self, point: Vector2) -> bool;
+    fn get_layer(&self) -> RenderLayer;
+
+    /** Key to sort objects by for drawing. Layers are drawn in order and objects are sorted by depth
+     * within their layer.
+     */
+    fn get_draw_order(&self) -> (RenderLayer, i32) {
+        (self.get_layer(), self.get_depth())
+    }
 }
 
 
@@ -80,33 +177,56 @@ pub struct GenericObject {
     pub b_box: Option<Rectangle>,
     pub depthmod: i32,
     pub colormod: Color,
+    pub animation: Option<AnimationState>,
+    pub animation_queue: VecDeque<String>,
+    pub mask: Option<Arc<SpriteMask>>,
+    pub flip_h: bool,
+    pub flip_v: bool,
+    pub rotation: i32,
+    pub scale: i32,
+    pub parent: Option<i32>,
+    pub parent_offset: Position,
+    pub state: Option<String>,
 }
 
 
 impl Object for GenericObject {
-    /** Simply draw the current sprite on the screen at the object's position. No color tinting or anything at all
+    /** Draw the current sprite at the object's position, flipped, rotated and scaled by the object's
+     * transform and tinted by its colormod.
      */
     fn draw(&self, rl: &mut RaylibTextureMode<RaylibDrawHandle>, debug: bool) {
         let tex = &self.object_data.0;
         let obj = &self.object_data.1;
+        // Negative source dimensions make raylib mirror the subimage
         let spr_rect = Rectangle {
             x: (obj.dim.0 * self.side_index) as f32,
             y: (obj.dim.1 * self.side) as f32,
-            width: obj.dim.0 as f32,
-            height: obj.dim.1 as f32,
+            width: (if self.flip_h { -obj.dim.0 } else { obj.dim.0 }) as f32,
+            height: (if self.flip_v { -obj.dim.1 } else { obj.dim.1 }) as f32,
         };
+        // Rotate around the middle of the transformed rectangle so the sprite stays inside it
+        let dest = rrect(
+            self.pos.x as f32 + self.width() as f32 / 2.,
+            self.pos.y as f32 + self.height() as f32 / 2.,
+            obj.dim.0 * self.scale,
+            obj.dim.1 * self.scale,
+        );
+        let origin = rvec2(dest.width / 2., dest.height / 2.);
 
-        rl.draw_texture_rec(tex, spr_rect, self.pos, self.colormod);
+        rl.draw_texture_pro(
+            tex,
+            spr_rect,
+            dest,
+            origin,
+            (self.rotation * 90) as f32,
+            self.colormod,
+        );
         if debug {
-            rl.draw_rectangle_lines_ex(
-                rrect(self.pos.x, self.pos.y, spr_rect.width, spr_rect.height),
-                1,
-                Color::BLACK,
-            );
+            rl.draw_rectangle_lines_ex(self.get_obj_rect(), 1, Color::BLACK);
             rl.draw_line(
                 self.pos.x,
                 self.get_depth(),
-                self.pos.x + spr_rect.width as i32,
+                self.pos.x + self.width(),
                 self.get_depth(),
                 Color::RED,
             );
@@ -116,10 +236,15 @@ impl Object for GenericObject {
     /** Change the sprite if the object supports that.
      */
     fn do_step(&mut self, frame_no: i32) {
-        if self.side_shift_speed != 0 && frame_no % self.side_shift_speed == 0 {
+        if self.animation.is_some() {
+            self.step_animation();
+        } else if self.side_shift_speed != 0 && frame_no % self.side_shift_speed == 0 {
             self.inc_index();
         }
-        self.depth = self.pos.y;
+        // Stacked objects take their depth from what they sit on, see `move_attached()`
+        if self.parent.is_none() {
+            self.depth = self.pos.y;
+        }
     }
 
     fn collide(&self, other: Option<&Rectangle>) -> bool {
@@ -149,7 +274,7 @@ impl Object for GenericObject {
     }
 
     fn get_depth(&self) -> i32 {
-        self.depth + self.depthmod
+        self.depth + self.depthmod + self.transform_depth_offset()
     }
 
     fn get_id(&self) -> i32 {
@@ -157,12 +282,7 @@ impl Object for GenericObject {
     }
 
     fn get_obj_rect(&self) -> Rectangle {
-        rrect(
-            self.pos.x,
-            self.pos.y,
-            self.object_data.1.dim.0,
-            self.object_data.1.dim.1,
-        )
+        rrect(self.pos.x, self.pos.y, self.width(), self.height())
     }
 
     fn get_collision_rect(&self) -> Rectangle {
@@ -177,6 +297,27 @@ impl Object for GenericObject {
             self.get_obj_rect()
         }
     }
+
+    /** Check whether the point lands on a visible pixel of the current sprite. Objects without a mask
+     * fall back to their sprite's rectangle.
+     */
+    fn contains_point(&self, point: Vector2) -> bool {
+        if !self.get_obj_rect().check_collision_point_rec(point) {
+            return false;
+        }
+        match &self.mask {
+            Some(mask) => {
+                let (x, y) =
+                    self.sprite_pixel_at(point.x as i32 - self.pos.x, point.y as i32 - self.pos.y);
+                mask.is_opaque(self.side, self.side_index, x, y)
+            }
+            None => true,
+        }
+    }
+
+    fn get_layer(&self) -> RenderLayer {
+        self.object_data.1.layer
+    }
 }
 
 impl PartialEq for GenericObject {
@@ -202,14 +343,25 @@ impl GenericObject {
     /** Create a new instance of an object of given type with a unique ID. The position the object is created in is either given by the user or is (0,0).
      */
     pub fn new(id: i32, obj_type: i32, pos: Option<Position>) -> Self {
+        Self::try_new(id, obj_type, pos).expect("Bad object type ID!")
+    }
+
+    /** Like `new()`, but returns an error instead of panicking if there is no object type `obj_type`, for
+     * objects read from somewhere that can't be trusted, like a save file or the clipboard.
+     */
+    pub fn try_new(
+        id: i32,
+        obj_type: i32,
+        pos: Option<Position>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         let data = Arc::clone(
             LOADED_TEXTURES
                 .lock()
                 .expect("Unable to lock LOADED_TEXTURES mutex!")
                 .get(&obj_type)
-                .expect("Bad object type ID!"),
+                .ok_or_else(|| format!("unknown object type ID {}", obj_type))?,
         );
-        Self {
+        let mut obj = Self {
             obj_id: obj_type,
             id,
             pos: pos.unwrap_or_default(),
@@ -218,14 +370,29 @@ impl GenericObject {
             side_index: 0,
             object_data: Arc::clone(&data),
             side_shift_speed: data.1.image_speed.unwrap_or(0),
-            b_box: data
-                .1
-                .default_b_box
-                .as_ref()
-                .map(|v| rrect(v.0, v.1, v.2, v.3)),
+            b_box: data.1.b_box_for(0, 0).map(|v| rrect(v.0, v.1, v.2, v.3)),
             depthmod: 0,
             colormod: Color::WHITE,
+            animation: None,
+            animation_queue: VecDeque::new(),
+            mask: SPRITE_MASKS
+                .lock()
+                .expect("Unable to lock SPRITE_MASKS mutex!")
+                .get(&obj_type)
+                .map(Arc::clone),
+            flip_h: false,
+            flip_v: false,
+            rotation: 0,
+            scale: 1,
+            parent: None,
+            parent_offset: Position::default(),
+            state: None,
+        };
+        // Objects with interaction states start out in the first one
+        if let Some(first) = data.1.states.first() {
+            obj.set_state(&first.name);
         }
+        Ok(obj)
     }
 
     /** Set which sprite side the object is using
@@ -236,6 +403,7 @@ impl GenericObject {
         if self.side >= self.object_data.1.sides {
             panic!("Attempting to set side greater than maximum!");
         }
+        self.refresh_b_box();
         self
     }
 
@@ -246,9 +414,28 @@ impl GenericObject {
         if self.side_index >= self.object_data.1.img_per_side {
             panic!("Attempting to set image index greater than maximum!");
         }
+        self.refresh_b_box();
         self
     }
 
+    /** Like `set_side()` and `set_index()` together, but returns an error instead of panicking if the
+     * spritesheet has no such side or subimage, for objects read from somewhere that can't be trusted.
+     */
+    pub fn try_set_sprite(
+        &mut self,
+        side: i32,
+        index: i32,
+    ) -> Result<&mut Self, Box<dyn std::error::Error>> {
+        let conf = &self.object_data.1;
+        if !(0..conf.sides).contains(&side) || !(0..conf.img_per_side).contains(&index) {
+            return Err(format!("{} has no subimage {} on side {}", conf.name, index, side).into());
+        }
+        self.side = side;
+        self.side_index = index;
+        self.refresh_b_box();
+        Ok(self)
+    }
+
     /** Set the frequency with which the sprite subimage is incremented. Lower values increment faster and 0 does not increment.
      */
     pub fn set_shift(&mut self, shift: i32) -> &mut Self {
@@ -263,6 +450,7 @@ impl GenericObject {
         if self.side_index >= self.object_data.1.img_per_side {
             self.side_index = 0;
         }
+        self.refresh_b_box();
         self
     }
 
@@ -273,6 +461,7 @@ impl GenericObject {
         if self.side_index < 0 {
             self.side_index = self.object_data.1.img_per_side - 1;
         }
+        self.refresh_b_box();
         self
     }
 
@@ -283,6 +472,7 @@ impl GenericObject {
         if self.side >= self.object_data.1.sides {
             self.side = 0;
         }
+        self.refresh_b_box();
         self
     }
 
@@ -293,6 +483,7 @@ impl GenericObject {
         if self.side < 0 {
             self.side = self.object_data.1.sides - 1;
         }
+        self.refresh_b_box();
         self
     }
 
@@ -352,19 +543,446 @@ impl GenericObject {
         self.object_data.1.image_speed.unwrap_or(0)
     }
 
+    /** Start playing the named animation clip from its first frame, dropping any queued clips. Returns
+     * false and leaves the object untouched if its type has no clip with that name.
+     */
+    pub fn play_animation(&mut self, name: &str) -> bool {
+        let data = Arc::clone(&self.object_data);
+        let clip = match data.1.get_animation(name) {
+            Some(c) if !c.frames.is_empty() => c,
+            _ => return false,
+        };
+
+        let state = AnimationState::new(name);
+        if let Some(frame) = state.current_frame(clip) {
+            self.show_frame(frame);
+        }
+        self.animation = Some(state);
+        self.animation_queue.clear();
+        true
+    }
+
+    /** Play the named clip once the current clip finishes or completes a cycle. Plays it right away if
+     * nothing is playing.
+     */
+    pub fn queue_animation(&mut self, name: &str) -> &mut Self {
+        if self.is_animation_finished() {
+            self.play_animation(name);
+        } else {
+            self.animation_queue.push_back(name.to_string());
+        }
+        self
+    }
+
+    /** Stop the current clip and forget all queued clips. The current subimage stays on screen.
+     */
+    pub fn stop_animation(&mut self) -> &mut Self {
+        self.animation = None;
+        self.animation_queue.clear();
+        self
+    }
+
+    /** Check whether the object changes subimage by itself, through a clip or its image speed. Its
+     * bounding box can change every step while it does.
+     */
+    pub fn is_animating(&self) -> bool {
+        self.animation.is_some() || self.side_shift_speed != 0
+    }
+
+    /** Return the name of the clip that is currently playing.
+     */
+    pub fn current_animation(&self) -> Option<&str> {
+        self.animation.as_ref().map(|a| a.name.as_str())
+    }
+
+    /** Check whether no clip is playing or the current clip was a one-shot that has ended.
+     */
+    pub fn is_animation_finished(&self) -> bool {
+        self.animation.as_ref().map_or(true, |a| a.finished)
+    }
+
+    /** Check whether the object's type defines a clip with the given name.
+     */
+    pub fn has_animation(&self, name: &str) -> bool {
+        self.object_data.1.get_animation(name).is_some()
+    }
+
+    /** Switch the object into the named interaction state, showing its frame or playing its clip.
+     * Returns false and leaves the object untouched if its type has no state with that name.
+     */
+    pub fn set_state(&mut self, name: &str) -> bool {
+        let data = Arc::clone(&self.object_data);
+        let state = match data.1.get_state(name) {
+            Some(s) => s,
+            None => return false,
+        };
+
+        if let Some(clip) = &state.animation {
+            self.play_animation(clip);
+        } else if let Some(frame) = state.frame {
+            self.stop_animation();
+            self.show_frame(frame);
+        }
+        self.state = Some(state.name.clone());
+        true
+    }
+
+    /** Interact with the object, switching it to its next state. Returns false if the object type has
+     * no states to switch between.
+     */
+    pub fn interact(&mut self) -> bool {
+        let data = Arc::clone(&self.object_data);
+        match data.1.next_state(self.get_state()) {
+            Some(next) => self.set_state(&next.name),
+            None => false,
+        }
+    }
+
+    /** Return the name of the interaction state the object is in.
+     */
+    pub fn get_state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+
+    /** Check whether the player can interact with the object.
+     */
+    pub fn is_interactive(&self) -> bool {
+        !self.object_data.1.states.is_empty()
+    }
+
+    /** Advance the current clip by a frame, moving on to the next queued clip when it ends a cycle.
+     */
+    fn step_animation(&mut self) {
+        let data = Arc::clone(&self.object_data);
+        let (cycle_done, frame) = match &mut self.animation {
+            Some(state) => match data.1.get_animation(&state.name) {
+                Some(clip) => (state.step(clip), state.current_frame(clip)),
+                None => (true, None),
+            },
+            None => return,
+        };
+
+        if let Some(frame) = frame {
+            self.show_frame(frame);
+        }
+        if cycle_done {
+            if let Some(next) = self.animation_queue.pop_front() {
+                // play_animation() clears the queue, so keep whatever comes after the next clip.
+                let rest = std::mem::take(&mut self.animation_queue);
+                self.play_animation(&next);
+                self.animation_queue = rest;
+            }
+        }
+    }
+
+    /** Show a subimage picked by an animation clip, ignoring frames the spritesheet doesn't have.
+     */
+    fn show_frame(&mut self, frame: i32) {
+        if frame >= 0 && frame < self.object_data.1.img_per_side {
+            self.side_index = frame;
+            self.refresh_b_box();
+        }
+    }
+
+    /** Pick up the bounding box for the current side and subimage and transform it to match the sprite.
+     * Needs to be called whenever the side, subimage or transform changes.
+     */
+    pub fn refresh_b_box(&mut self) -> &mut Self {
+        self.b_box = self
+            .object_data
+            .1
+            .b_box_for(self.side, self.side_index)
+            .map(|v| self.transform_local_rect(rrect(v.0, v.1, v.2, v.3)));
+        self
+    }
+
+    /** Mirror the object left to right.
+     */
+    pub fn flip_horizontal(&mut self) -> &mut Self {
+        self.flip_h = !self.flip_h;
+        self.refresh_b_box();
+        self
+    }
+
+    /** Mirror the object top to bottom.
+     */
+    pub fn flip_vertical(&mut self) -> &mut Self {
+        self.flip_v = !self.flip_v;
+        self.refresh_b_box();
+        self
+    }
+
+    /** Rotate the object a quarter turn clockwise, wrapping back to no rotation after a full turn.
+     */
+    pub fn rotate_cw(&mut self) -> &mut Self {
+        self.rotation = (self.rotation + 1) % 4;
+        self.refresh_b_box();
+        self
+    }
+
+    /** Rotate the object a quarter turn counter clockwise.
+     */
+    pub fn rotate_ccw(&mut self) -> &mut Self {
+        self.rotation = (self.rotation + 3) % 4;
+        self.refresh_b_box();
+        self
+    }
+
+    /** Set how many screen pixels each sprite pixel covers. Values below 1 are treated as 1.
+     */
+    pub fn set_scale(&mut self, scale: i32) -> &mut Self {
+        self.scale = scale.max(1);
+        self.refresh_b_box();
+        self
+    }
+
+    /** Map a rectangle given relative to the untransformed sprite into the object's transformed space.
+     * Flips are applied first, then the rotation, then the scale.
+     */
+    pub fn transform_local_rect(&self, rect: Rectangle) -> Rectangle {
+        let w = self.base_width() as f32;
+        let h = self.base_height() as f32;
+        let x = if self.flip_h {
+            w - rect.x - rect.width
+        } else {
+            rect.x
+        };
+        let y = if self.flip_v {
+            h - rect.y - rect.height
+        } else {
+            rect.y
+        };
+        let rotated = match self.rotation {
+            1 => rrect(h - y - rect.height, x, rect.height, rect.width),
+            2 => rrect(
+                w - x - rect.width,
+                h - y - rect.height,
+                rect.width,
+                rect.height,
+            ),
+            3 => rrect(y, w - x - rect.width, rect.height, rect.width),
+            _ => rrect(x, y, rect.width, rect.height),
+        };
+        let scale = self.scale as f32;
+        rrect(
+            rotated.x * scale,
+            rotated.y * scale,
+            rotated.width * scale,
+            rotated.height * scale,
+        )
+    }
+
+    /** Map a pixel relative to the object's position back to the pixel of the untransformed sprite that
+     * is drawn there. The inverse of `transform_local_rect()`.
+     */
+    pub fn sprite_pixel_at(&self, x: i32, y: i32) -> (i32, i32) {
+        let w = self.base_width();
+        let h = self.base_height();
+        let x = x.div_euclid(self.scale);
+        let y = y.div_euclid(self.scale);
+        let (px, py) = match self.rotation {
+            1 => (y, h - 1 - x),
+            2 => (w - 1 - x, h - 1 - y),
+            3 => (w - 1 - y, x),
+            _ => (x, y),
+        };
+        (
+            if self.flip_h { w - 1 - px } else { px },
+            if self.flip_v { h - 1 - py } else { py },
+        )
+    }
+
+    /** Sit on top of another object. The child keeps its current offset from the parent and follows it
+     * around from then on.
+     */
+    pub fn attach_to(&mut self, parent: &GenericObject) -> &mut Self {
+        self.parent = Some(parent.get_id());
+        self.parent_offset = Position::new(self.pos.x - parent.pos.x, self.pos.y - parent.pos.y);
+        self
+    }
+
+    /** Stop following the parent object. The object stays where it is.
+     */
+    pub fn detach(&mut self) -> &mut Self {
+        self.parent = None;
+        self.parent_offset = Position::default();
+        self
+    }
+
+    /** Get the regions in screenspace that other objects can be set down on, transformed to match the
+     * sprite.
+     */
+    pub fn get_surface_rects(&self) -> Vec<Rectangle> {
+        self.object_data
+            .1
+            .surfaces
+            .iter()
+            .map(|s| {
+                let r = self.transform_local_rect(rrect(s.rect.0, s.rect.1, s.rect.2, s.rect.3));
+                rrect(
+                    r.x + self.pos.x as f32,
+                    r.y + self.pos.y as f32,
+                    r.width,
+                    r.height,
+                )
+            })
+            .collect()
+    }
+
+    /** Check whether an object standing at `point` would be standing on one of this object's surfaces.
+     */
+    pub fn surface_contains(&self, point: Vector2) -> bool {
+        self.get_surface_rects()
+            .iter()
+            .any(|r| r.check_collision_point_rec(point))
+    }
+
+    /** The point an object stands on, which is the bottom middle of its collision box. Used to decide
+     * which surface a stackable object was put down on.
+     */
+    pub fn get_footprint(&self) -> Vector2 {
+        let rect = self.get_collision_rect();
+        rvec2(rect.x + rect.width / 2., rect.y + rect.height - 1.)
+    }
+
+    /** Rotating or scaling changes how far the sprite reaches down, so the depth line moves with its
+     * bottom edge.
+     */
+    fn transform_depth_offset(&self) -> i32 {
+        self.height() - self.base_height()
+    }
+
     pub fn update_depth(&mut self) -> &mut Self {
         self.depth = self.pos.y;
         self
     }
 
+    /** Give the object a new ID, for copies of an object that have to be told apart from the original.
+     */
+    pub fn set_id(&mut self, id: i32) -> &mut Self {
+        self.id = id;
+        self
+    }
+
+    /** Width of the object on screen, after rotation and scale.
+     */
     pub fn width(&self) -> i32 {
-        self.object_data.1.dim.0
+        if self.rotation % 2 == 0 {
+            self.base_width() * self.scale
+        } else {
+            self.base_height() * self.scale
+        }
     }
+    /** Height of the object on screen, after rotation and scale.
+     */
     pub fn height(&self) -> i32 {
+        if self.rotation % 2 == 0 {
+            self.base_height() * self.scale
+        } else {
+            self.base_width() * self.scale
+        }
+    }
+    /** Width of a single subimage in the spritesheet.
+     */
+    pub fn base_width(&self) -> i32 {
+        self.object_data.1.dim.0
+    }
+    /** Height of a single subimage in the spritesheet.
+     */
+    pub fn base_height(&self) -> i32 {
         self.object_data.1.dim.1
     }
 }
 
+/** Move every attached object to its parent and put it just above the parent's depth. Objects whose
+ * parent no longer exists, or that are part of a parent loop, are detached. Looks at every object, so it
+ * is for when a whole room has changed, like after loading or undoing. Use `move_attached()` when only a
+ * few objects have moved.
+ */
+pub fn update_attachments(objects: &[GenObj]) {
+    let by_id: HashMap<i32, GenObj> = objects
+        .iter()
+        .map(|o| (o.borrow().get_id(), o.clone()))
+        .collect();
+    let mut placed = HashSet::new();
+    for obj in objects.iter() {
+        place_attached(obj, &by_id, &mut placed, &mut HashSet::new());
+    }
+}
+
+/** Bring whatever is stacked on the objects in `moved` along with them, however high the stacks are,
+ * putting each just above the depth of what it sits on. Returns the objects that were brought along.
+ */
+pub fn move_attached(objects: &[GenObj], moved: &[GenObj]) -> Vec<GenObj> {
+    let mut followers: Vec<GenObj> = Vec::new();
+    let mut parents = moved.to_vec();
+    while let Some(parent) = parents.pop() {
+        let (parent_id, parent_pos, parent_depth) = {
+            let p = parent.borrow();
+            (p.get_id(), p.pos, p.get_depth())
+        };
+        for obj in objects.iter() {
+            let is_child = !Rc::ptr_eq(obj, &parent) && obj.borrow().parent == Some(parent_id);
+            // A parent loop would go round forever, so each object is only brought along once
+            if !is_child || followers.iter().any(|f| Rc::ptr_eq(f, obj)) {
+                continue;
+            }
+            {
+                let mut o = obj.borrow_mut();
+                o.pos = parent_pos.offset(o.parent_offset.x, o.parent_offset.y);
+                o.depth = parent_depth + 1 - o.transform_depth_offset();
+            }
+            followers.push(obj.clone());
+            parents.push(obj.clone());
+        }
+    }
+    followers
+}
+
+/** Place an object's ancestors before the object itself so that stacks of any height follow along.
+ */
+fn place_attached(
+    obj: &GenObj,
+    by_id: &HashMap<i32, GenObj>,
+    placed: &mut HashSet<i32>,
+    visiting: &mut HashSet<i32>,
+) {
+    let (id, parent_id) = {
+        let o = obj.borrow();
+        (o.get_id(), o.parent)
+    };
+    if placed.contains(&id) {
+        return;
+    }
+    let parent_id = match parent_id {
+        Some(p) => p,
+        None => {
+            placed.insert(id);
+            return;
+        }
+    };
+
+    visiting.insert(id);
+    let parent = match by_id.get(&parent_id) {
+        Some(p) if !visiting.contains(&parent_id) && !Rc::ptr_eq(p, obj) => p,
+        _ => {
+            obj.borrow_mut().detach();
+            placed.insert(id);
+            return;
+        }
+    };
+    place_attached(parent, by_id, placed, visiting);
+
+    let (parent_pos, parent_depth) = {
+        let p = parent.borrow();
+        (p.pos, p.get_depth())
+    };
+    let mut o = obj.borrow_mut();
+    o.pos = parent_pos.offset(o.parent_offset.x, o.parent_offset.y);
+    o.depth = parent_depth + 1 - o.transform_depth_offset();
+    placed.insert(id);
+}
+
 /* SAVE IMPLEMENTATIONS */
 impl Saveable<Self> for Position {
     fn to_bytes(&self) -> Vec<u8> {
@@ -374,6 +992,7 @@ impl Saveable<Self> for Position {
     }
 
     fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn std::error::Error>> {
+        read_slice(bytes, 8)?;
         let result = Position {
             x: i32::from_bytes(&bytes[0..4])?.0,
             y: i32::from_bytes(&bytes[4..8])?.0,
@@ -391,10 +1010,58 @@ impl Saveable<Self> for GenericObject {
         result.extend(self.side.to_bytes());
         result.extend(self.colormod.to_bytes());
         result.extend(self.depthmod.to_bytes());
+        result.extend(self.flip_h.to_bytes());
+        result.extend(self.flip_v.to_bytes());
+        result.extend(self.rotation.to_bytes());
+        result.extend(self.scale.to_bytes());
+        result.extend(self.parent.to_bytes());
+        result.extend(self.parent_offset.to_bytes());
+        result.extend(self.state.to_bytes());
         result
     }
 
     fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn std::error::Error>> {
+        read_slice(bytes, 48)?; // Everything before the parent has a fixed size
+        let id = i32::from_bytes(&bytes[0..4])?;
+        let obj_id = i32::from_bytes(&bytes[4..8])?;
+        let pos = Position::from_bytes(&bytes[8..16])?;
+        let side_index = i32::from_bytes(&bytes[16..20])?;
+        let side = i32::from_bytes(&bytes[20..24])?;
+        let colormod = Color::from_bytes(&bytes[24..28])?;
+        let depthmod = i32::from_bytes(&bytes[28..32])?;
+        let flip_h = bool::from_bytes(&bytes[32..36])?;
+        let flip_v = bool::from_bytes(&bytes[36..40])?;
+        let rotation = i32::from_bytes(&bytes[40..44])?;
+        let scale = i32::from_bytes(&bytes[44..48])?;
+        let parent = Option::<i32>::from_bytes(&bytes[48..])?;
+        let parent_offset = Position::from_bytes(&bytes[48 + parent.1..])?;
+        let state_start = 48 + parent.1 + parent_offset.1;
+        let state = Option::<String>::from_bytes(&bytes[state_start..])?;
+        let mut obj = GenericObject::try_new(id.0, obj_id.0, Some(pos.0))?;
+        obj.try_set_sprite(side.0, side_index.0)?;
+        obj.colormod = colormod.0;
+        obj.depthmod = depthmod.0;
+        obj.flip_h = flip_h.0;
+        obj.flip_v = flip_v.0;
+        obj.rotation = rotation.0.rem_euclid(4);
+        obj.scale = scale.0.max(1);
+        obj.parent = parent.0;
+        obj.parent_offset = parent_offset.0;
+        obj.refresh_b_box();
+        // The state goes last, so the frame it shows wins over the subimage saved with it
+        if let Some(name) = &state.0 {
+            obj.set_state(name);
+        }
+        Ok(SaveInfo(obj, state_start + state.1))
+    }
+}
+
+impl GenericObject {
+    /** Read an object from a save file without a header, written before objects could be flipped,
+     * rotated, scaled, stacked or switched between states. Those records are always 32 bytes.
+     */
+    pub fn from_legacy_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn std::error::Error>> {
+        read_slice(bytes, 32)?;
         let id = i32::from_bytes(&bytes[0..4])?;
         let obj_id = i32::from_bytes(&bytes[4..8])?;
         let pos = Position::from_bytes(&bytes[8..16])?;
@@ -402,9 +1069,8 @@ impl Saveable<Self> for GenericObject {
         let side = i32::from_bytes(&bytes[20..24])?;
         let colormod = Color::from_bytes(&bytes[24..28])?;
         let depthmod = i32::from_bytes(&bytes[28..32])?;
-        let mut obj = GenericObject::new(id.0, obj_id.0, Some(pos.0));
-        obj.side_index = side_index.0;
-        obj.side = side.0;
+        let mut obj = GenericObject::try_new(id.0, obj_id.0, Some(pos.0))?;
+        obj.try_set_sprite(side.0, side_index.0)?;
         obj.colormod = colormod.0;
         obj.depthmod = depthmod.0;
         Ok(SaveInfo(obj, 32))
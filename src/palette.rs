@@ -0,0 +1,212 @@
+//! # Palette
+//! `palette` keeps the colours the player tints objects with: the colours used most recently, and named
+//! palettes of saved swatches. Both are kept in `PALETTE_PATH` so they are still there next game.
+
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+
+/// File the recent colours and the palettes are saved to and loaded from.
+pub const PALETTE_PATH: &str = "data/palettes.toml";
+
+/// How many recently used colours are remembered.
+pub const MAX_RECENT: usize = 12;
+
+/// A colour as it is written to the palette file.
+pub type Rgba = (u8, u8, u8, u8);
+
+pub fn to_rgba(color: Color) -> Rgba {
+    (color.r, color.g, color.b, color.a)
+}
+
+pub fn from_rgba(rgba: Rgba) -> Color {
+    Color::new(rgba.0, rgba.1, rgba.2, rgba.3)
+}
+
+/** Make a colour out of a hue in degrees, a saturation and a value from 0 to 1, and an alpha.
+ */
+pub fn hsv_to_color(hue: f32, saturation: f32, value: f32, alpha: u8) -> Color {
+    let hue = hue.rem_euclid(360.) / 60.;
+    let saturation = saturation.clamp(0., 1.);
+    let value = value.clamp(0., 1.);
+    let chroma = value * saturation;
+    let x = chroma * (1. - (hue % 2. - 1.).abs());
+    let (r, g, b) = match hue as i32 {
+        0 => (chroma, x, 0.),
+        1 => (x, chroma, 0.),
+        2 => (0., chroma, x),
+        3 => (0., x, chroma),
+        4 => (x, 0., chroma),
+        _ => (chroma, 0., x),
+    };
+    let m = value - chroma;
+    let channel = |c: f32| ((c + m) * 255.).round() as u8;
+    Color::new(channel(r), channel(g), channel(b), alpha)
+}
+
+/** Split a colour into its hue in degrees, and its saturation and value from 0 to 1. Greys have a hue
+ * of 0.
+ */
+pub fn color_to_hsv(color: Color) -> (f32, f32, f32) {
+    let r = color.r as f32 / 255.;
+    let g = color.g as f32 / 255.;
+    let b = color.b as f32 / 255.;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let hue = if delta == 0. {
+        0.
+    } else if max == r {
+        60. * ((g - b) / delta).rem_euclid(6.)
+    } else if max == g {
+        60. * ((b - r) / delta + 2.)
+    } else {
+        60. * ((r - g) / delta + 4.)
+    };
+    let saturation = if max == 0. { 0. } else { delta / max };
+    (hue, saturation, max)
+}
+
+/** A named set of colours saved by the player.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    #[serde(default)]
+    pub colors: Vec<Rgba>,
+}
+
+/** Every palette, along with the colours used most recently, newest first.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Palettes {
+    #[serde(default)]
+    pub recent: Vec<Rgba>,
+    #[serde(default)]
+    pub palettes: Vec<Palette>,
+}
+
+impl Default for Palettes {
+    fn default() -> Self {
+        Self {
+            recent: Vec::new(),
+            palettes: vec![classic()],
+        }
+    }
+}
+
+impl Palettes {
+    /** Read the palettes from a TOML file.
+     */
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let confstr = fs::read_to_string(path)?;
+        Ok(toml::from_str(confstr.as_str())?)
+    }
+
+    /** Read the palettes from a TOML file, falling back to the built in palette if it can't be read or
+     * has no palettes in it.
+     */
+    pub fn load_or_default(path: &str) -> Self {
+        match Self::load(path) {
+            Ok(p) if !p.palettes.is_empty() => p,
+            Ok(p) => Self {
+                recent: p.recent,
+                ..Self::default()
+            },
+            Err(e) => {
+                println!("Unable to load palettes from {}: {}", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    /** Write the palettes to a TOML file.
+     */
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /** Put a colour at the front of the recent colours, forgetting the oldest one if there are too many.
+     */
+    pub fn use_color(&mut self, color: Color) {
+        let rgba = to_rgba(color);
+        self.recent.retain(|c| *c != rgba);
+        self.recent.insert(0, rgba);
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /** Get the colours of a palette. A palette that doesn't exist has none.
+     */
+    pub fn colors(&self, palette: usize) -> Vec<Color> {
+        self.palettes.get(palette).map_or_else(Vec::new, |p| {
+            p.colors.iter().map(|c| from_rgba(*c)).collect()
+        })
+    }
+
+    /** Add a colour to the end of a palette, unless it already has it.
+     */
+    pub fn add_color(&mut self, palette: usize, color: Color) {
+        if let Some(p) = self.palettes.get_mut(palette) {
+            let rgba = to_rgba(color);
+            if !p.colors.contains(&rgba) {
+                p.colors.push(rgba);
+            }
+        }
+    }
+
+    pub fn remove_color(&mut self, palette: usize, index: usize) {
+        if let Some(p) = self.palettes.get_mut(palette) {
+            if index < p.colors.len() {
+                p.colors.remove(index);
+            }
+        }
+    }
+
+    /** Add an empty palette and get its index.
+     */
+    pub fn new_palette(&mut self) -> usize {
+        let name = format!("Palette {}", self.palettes.len() + 1);
+        self.palettes.push(Palette {
+            name,
+            colors: Vec::new(),
+        });
+        self.palettes.len() - 1
+    }
+}
+
+/// The colours that could be picked before palettes existed.
+fn classic() -> Palette {
+    let colors = [
+        Color::WHITE,
+        Color::RAYWHITE,
+        Color::LIGHTGRAY,
+        Color::GRAY,
+        Color::DARKGRAY,
+        Color::BLACK,
+        Color::YELLOW,
+        Color::GOLD,
+        Color::ORANGE,
+        Color::PINK,
+        Color::RED,
+        Color::MAROON,
+        Color::MAGENTA,
+        Color::SKYBLUE,
+        Color::BLUE,
+        Color::DARKBLUE,
+        Color::PURPLE,
+        Color::VIOLET,
+        Color::DARKPURPLE,
+        Color::BEIGE,
+        Color::BROWN,
+        Color::DARKBROWN,
+        Color::DARKGREEN,
+        Color::GREEN,
+        Color::LIME,
+    ];
+    Palette {
+        name: "Classic".to_string(),
+        colors: colors.iter().map(|c| to_rgba(*c)).collect(),
+    }
+}
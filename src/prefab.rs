@@ -0,0 +1,212 @@
+//! # Prefab
+//! `prefab` saves groups of objects, like a desk with a chair and a plant, so that they can be placed
+//! again as one unit. Prefabs are TOML files in `PREFAB_DIR` and are listed from the `PREFAB_CATEGORY`
+//! button of the furniture menu.
+
+use crate::clipboard;
+use crate::object::*;
+use crate::snap::Snap;
+use crate::util;
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Directory prefabs are saved to and loaded from.
+pub const PREFAB_DIR: &str = "prefabs";
+
+/// Name of the furniture menu button that lists the prefabs.
+pub const PREFAB_CATEGORY: &str = "Prefabs";
+
+/** One object of a prefab. `object` is the name of its object type and `offset` is where it sits
+ * relative to the top left of the prefab. `parent` is the index of the piece it is sitting on.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PrefabPiece {
+    pub object: String,
+    pub offset: (i32, i32),
+    #[serde(default)]
+    pub side: i32,
+    #[serde(default)]
+    pub subimage: i32,
+    #[serde(default = "white")]
+    pub color: (u8, u8, u8, u8),
+    #[serde(default)]
+    pub depthmod: i32,
+    #[serde(default)]
+    pub flip_h: bool,
+    #[serde(default)]
+    pub flip_v: bool,
+    #[serde(default)]
+    pub rotation: i32,
+    #[serde(default = "one")]
+    pub scale: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+}
+
+fn white() -> (u8, u8, u8, u8) {
+    (255, 255, 255, 255)
+}
+
+fn one() -> i32 {
+    1
+}
+
+/** A named group of objects that can be placed as one unit.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Prefab {
+    pub name: String,
+    pub pieces: Vec<PrefabPiece>,
+}
+
+impl Prefab {
+    /** Read a prefab from its TOML file.
+     */
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let confstr = fs::read_to_string(path)?;
+        Ok(toml::from_str(confstr.as_str())?)
+    }
+
+    /** Read every prefab in a directory, sorted by name. Files that can't be read are skipped.
+     */
+    pub fn load_all(dir: &str) -> Vec<Self> {
+        let mut result = Vec::new();
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().map_or(true, |e| e != "toml") {
+                    continue;
+                }
+                match Prefab::load(&path.to_string_lossy()) {
+                    Ok(prefab) => result.push(prefab),
+                    Err(e) => println!("Unable to load prefab {}: {}", path.display(), e),
+                }
+            }
+        }
+        result.sort_by(|a, b| a.name.cmp(&b.name));
+        result
+    }
+
+    /** Make a prefab out of some objects. Their positions are kept relative to the top left one, and
+     * objects sitting on another object of the group stay on it.
+     */
+    pub fn from_objects(name: &str, objs: &[GenObj]) -> Self {
+        let origin_x = objs.iter().map(|o| o.borrow().pos.x).min().unwrap_or(0);
+        let origin_y = objs.iter().map(|o| o.borrow().pos.y).min().unwrap_or(0);
+        let index_of: HashMap<i32, usize> = objs
+            .iter()
+            .enumerate()
+            .map(|(i, o)| (o.borrow().get_id(), i))
+            .collect();
+
+        let pieces = objs
+            .iter()
+            .map(|o| {
+                let o = o.borrow();
+                PrefabPiece {
+                    object: o.object_data.1.name.clone(),
+                    offset: (o.pos.x - origin_x, o.pos.y - origin_y),
+                    side: o.side,
+                    subimage: o.side_index,
+                    color: (o.colormod.r, o.colormod.g, o.colormod.b, o.colormod.a),
+                    depthmod: o.depthmod,
+                    flip_h: o.flip_h,
+                    flip_v: o.flip_v,
+                    rotation: o.rotation,
+                    scale: o.scale,
+                    state: o.state.clone(),
+                    parent: o.parent.and_then(|p| index_of.get(&p).copied()),
+                }
+            })
+            .collect();
+
+        Self {
+            name: name.to_string(),
+            pieces,
+        }
+    }
+
+    /** Write the prefab to `dir`, named after the prefab. Returns the path it was written to. A prefab
+     * of the same name is replaced, but a file holding a different prefab whose name maps to the same
+     * file name is left alone and an error is returned instead.
+     */
+    pub fn save(&self, dir: &str) -> Result<String, Box<dyn Error>> {
+        fs::create_dir_all(dir)?;
+        let file_name: String = self
+            .name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = Path::new(dir).join(format!("{}.toml", file_name));
+        if path.exists() {
+            match Prefab::load(&path.to_string_lossy()) {
+                Ok(other) if other.name == self.name => (),
+                Ok(other) => {
+                    return Err(format!(
+                        "{} already holds the prefab {}",
+                        path.display(),
+                        other.name
+                    )
+                    .into())
+                }
+                Err(e) => return Err(format!("{} already exists: {}", path.display(), e).into()),
+            }
+        }
+        fs::write(&path, toml::to_string(self)?)?;
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    /** Make the objects of the prefab, centred on `at` and lined up with the `snap` grid, with fresh IDs
+     * from `next_id`.
+     */
+    pub fn spawn(
+        &self,
+        at: Vector2,
+        next_id: &mut i32,
+        snap: &Snap,
+    ) -> Result<Vec<GenObj>, Box<dyn Error>> {
+        let mut objs = Vec::new();
+        for (i, piece) in self.pieces.iter().enumerate() {
+            let obj_id = util::find_object_id(&piece.object).ok_or_else(|| {
+                format!("unknown object {} in prefab {}", piece.object, self.name)
+            })?;
+            let mut obj = GenericObject::new(
+                i as i32,
+                obj_id,
+                Some(Position::new(piece.offset.0, piece.offset.1)),
+            );
+            // The file can be edited by hand, so the sprite is checked rather than trusted
+            obj.try_set_sprite(piece.side, piece.subimage)
+                .map_err(|e| format!("{} in prefab {}", e, self.name))?;
+            obj.colormod = Color::new(piece.color.0, piece.color.1, piece.color.2, piece.color.3);
+            obj.depthmod = piece.depthmod;
+            obj.flip_h = piece.flip_h;
+            obj.flip_v = piece.flip_v;
+            obj.rotation = piece.rotation.rem_euclid(4);
+            obj.scale = piece.scale.max(1);
+            // Pieces use their index as ID until they are placed, so parents can be found by index
+            if let Some(parent) = piece
+                .parent
+                .and_then(|p| self.pieces.get(p).map(|q| (p, q)))
+            {
+                obj.parent = Some(parent.0 as i32);
+                obj.parent_offset = Position::new(
+                    piece.offset.0 - parent.1.offset.0,
+                    piece.offset.1 - parent.1.offset.1,
+                );
+            }
+            obj.refresh_b_box();
+            if let Some(name) = &piece.state {
+                obj.set_state(name);
+            }
+            objs.push(obj);
+        }
+        Ok(clipboard::place(objs, at, next_id, snap))
+    }
+}
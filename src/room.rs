@@ -0,0 +1,610 @@
+//! # Room
+//! `room` describes the layout of a room: how big it is, where its walls run, where the doors in those
+//! walls are and what the floor is tiled with. Wall objects are generated from the layout instead of
+//! being placed by hand, so rooms can be any size and shape.
+
+use crate::game::*;
+use crate::naomi::STEP_SIZE;
+use crate::object::*;
+use crate::save::*;
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+
+/// Object type ID of the wall piece used for horizontal walls.
+pub const WALL_OBJ_ID: i32 = 0;
+/// Object type ID of the wall piece used for vertical walls.
+pub const SIDE_WALL_OBJ_ID: i32 = 9;
+/// Width and height in pixels of a floor cell when the room doesn't say otherwise.
+pub const DEFAULT_TILE_SIZE: i32 = 32;
+
+fn default_tile_size() -> i32 {
+    DEFAULT_TILE_SIZE
+}
+
+/** A floor tile that can be painted onto floor cells. The image is drawn tinted, so one image can be
+ * used for several colours of tile.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TileConfig {
+    pub name: String,
+    pub image: String,
+    #[serde(default = "white")]
+    pub tint: (u8, u8, u8),
+}
+
+/** A wallpaper that can be put on a wall segment. Wallpapers tint the wall pieces of the segment.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WallpaperConfig {
+    pub name: String,
+    #[serde(default = "white")]
+    pub tint: (u8, u8, u8),
+}
+
+fn white() -> (u8, u8, u8) {
+    (255, 255, 255)
+}
+
+/** Every floor tile and wallpaper the player can paint with. Rooms refer to them by their index, so
+ * new entries should only ever be added to the end.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TileCatalogue {
+    #[serde(default)]
+    pub tiles: Vec<TileConfig>,
+    #[serde(default)]
+    pub wallpapers: Vec<WallpaperConfig>,
+}
+
+impl TileCatalogue {
+    /** Read the catalogue from a TOML file.
+     */
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let confstr = fs::read_to_string(path)?;
+        Ok(toml::from_str(confstr.as_str())?)
+    }
+
+    /** Get the colour the wall pieces of a segment are tinted with. No wallpaper, or one missing from
+     * the catalogue, leaves the walls untinted.
+     */
+    pub fn wallpaper_tint(&self, wallpaper: Option<usize>) -> Color {
+        match wallpaper.and_then(|w| self.wallpapers.get(w)) {
+            Some(w) => Color::new(w.tint.0, w.tint.1, w.tint.2, 255),
+            None => Color::WHITE,
+        }
+    }
+}
+
+/** A straight run of wall from `from` to `to`, in pixels. Only horizontal and vertical walls are
+ * supported. Wall pieces are placed with their top left corner on the segment, starting at the end
+ * nearest to the top left of the room and stopping before the far end.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WallSegment {
+    pub from: (i32, i32),
+    pub to: (i32, i32),
+    /// Index of the wallpaper in the tile catalogue, if the segment is papered.
+    pub wallpaper: Option<usize>,
+}
+
+impl WallSegment {
+    pub fn is_horizontal(&self) -> bool {
+        self.from.1 == self.to.1
+    }
+
+    pub fn is_vertical(&self) -> bool {
+        self.from.0 == self.to.0 && !self.is_horizontal()
+    }
+
+    /** Get the top left end of the segment.
+     */
+    pub fn start(&self) -> (i32, i32) {
+        (self.from.0.min(self.to.0), self.from.1.min(self.to.1))
+    }
+
+    /** Check whether a wall piece at `pos` was generated from this segment.
+     */
+    pub fn has_piece_at(&self, pos: Position) -> bool {
+        let (x, y) = self.start();
+        if self.is_horizontal() {
+            pos.y == y && pos.x >= x && pos.x < x + self.len()
+        } else if self.is_vertical() {
+            pos.x == x && pos.y >= y && pos.y < y + self.len()
+        } else {
+            false
+        }
+    }
+
+    /** Get the length of the segment in pixels.
+     */
+    pub fn len(&self) -> i32 {
+        (self.to.0 - self.from.0).abs() + (self.to.1 - self.from.1).abs()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/** An opening in one of the room's walls. `wall` is the index of the segment in `Room::walls`, and
+ * `offset` and `width` are measured in pixels along that segment from its top left end. Wall pieces
+ * overlapping the opening are left out. Walking through a door with a `target` leads to the room of
+ * that name.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DoorConfig {
+    pub wall: usize,
+    pub offset: i32,
+    pub width: i32,
+    pub target: Option<String>,
+}
+
+/** Layout of a single room. Loaded from a TOML file for a new game and saved with the game after that.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Room {
+    pub name: String,
+    /// Width and height of the room in pixels.
+    pub size: (i32, i32),
+    /// Path to the image used for floor cells whose tile isn't in the tile catalogue.
+    pub floor_tile: String,
+    /// Width and height in pixels of a floor cell.
+    #[serde(default = "default_tile_size")]
+    pub tile_size: i32,
+    /// Index in the tile catalogue of the tile used by each floor cell, row by row. Cells past the end
+    /// use the first tile.
+    #[serde(default)]
+    pub floor: Vec<usize>,
+    /// Where the top left corner of the room is drawn on the house map, in pixels.
+    #[serde(default)]
+    pub map_pos: (i32, i32),
+    #[serde(default)]
+    pub walls: Vec<WallSegment>,
+    #[serde(default)]
+    pub doors: Vec<DoorConfig>,
+}
+
+impl Room {
+    /** Create an empty room of the given size, with no walls or doors.
+     */
+    pub fn new(name: &str, width: i32, height: i32, floor_tile: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            size: (width, height),
+            floor_tile: floor_tile.to_string(),
+            tile_size: DEFAULT_TILE_SIZE,
+            floor: Vec::new(),
+            map_pos: (0, 0),
+            walls: Vec::new(),
+            doors: Vec::new(),
+        }
+    }
+
+    /** Read a room layout from a TOML file.
+     */
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let confstr = fs::read_to_string(path)?;
+        Ok(toml::from_str(confstr.as_str())?)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.size.0
+    }
+
+    pub fn height(&self) -> i32 {
+        self.size.1
+    }
+
+    /** Get the amount of floor cell columns and rows. Cells at the right and bottom edges are cut off
+     * if the room size isn't a multiple of the tile size.
+     */
+    pub fn floor_cells(&self) -> (i32, i32) {
+        let size = self.tile_size.max(1);
+        (
+            (self.width() + size - 1) / size,
+            (self.height() + size - 1) / size,
+        )
+    }
+
+    /** Get the floor cell under a point in the room, if the point is inside the room.
+     */
+    pub fn cell_at(&self, point: Vector2) -> Option<(i32, i32)> {
+        if point.x < 0. || point.y < 0. {
+            return None;
+        }
+        let size = self.tile_size.max(1) as f32;
+        let (col, row) = ((point.x / size) as i32, (point.y / size) as i32);
+        let (cols, rows) = self.floor_cells();
+        if col < cols && row < rows {
+            Some((col, row))
+        } else {
+            None
+        }
+    }
+
+    /** Get the catalogue index of the tile used by a floor cell.
+     */
+    pub fn tile_at(&self, col: i32, row: i32) -> usize {
+        let (cols, _) = self.floor_cells();
+        self.floor
+            .get((row * cols + col) as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /** Paint a floor cell with a tile from the catalogue. Returns true if the cell changed, in which case
+     * the floor image needs to be rebuilt.
+     */
+    pub fn set_tile(&mut self, col: i32, row: i32, tile: usize) -> bool {
+        let (cols, rows) = self.floor_cells();
+        if col < 0 || row < 0 || col >= cols || row >= rows || self.tile_at(col, row) == tile {
+            return false;
+        }
+        let index = (row * cols + col) as usize;
+        if self.floor.len() <= index {
+            self.floor.resize((cols * rows) as usize, 0);
+        }
+        self.floor[index] = tile;
+        true
+    }
+
+    /** Find the wall segment a generated wall piece belongs to.
+     */
+    pub fn wall_segment_of(&self, piece: &GenericObject) -> Option<usize> {
+        let horizontal = match piece.obj_id {
+            WALL_OBJ_ID => true,
+            SIDE_WALL_OBJ_ID => false,
+            _ => return None,
+        };
+        self.walls
+            .iter()
+            .position(|w| w.is_horizontal() == horizontal && w.has_piece_at(piece.pos))
+    }
+
+    /** Check whether a wall piece covering `start..start + len` along the given segment would overlap
+     * a door in that segment.
+     */
+    fn is_door(&self, wall: usize, start: i32, len: i32) -> bool {
+        self.doors
+            .iter()
+            .filter(|d| d.wall == wall)
+            .any(|d| start < d.offset + d.width && d.offset < start + len)
+    }
+
+    /** Get the area a door takes up in the room, which is the area the wall pieces it replaces would
+     * have covered.
+     */
+    pub fn door_rect(&self, door: usize) -> Option<Rectangle> {
+        let d = self.doors.get(door)?;
+        let seg = self.walls.get(d.wall)?;
+        let (x, y) = seg.start();
+        if seg.is_horizontal() {
+            let (_, h) = piece_dim(WALL_OBJ_ID);
+            Some(rrect(x + d.offset, y, d.width, h))
+        } else if seg.is_vertical() {
+            let (w, _) = piece_dim(SIDE_WALL_OBJ_ID);
+            Some(rrect(x, y + d.offset, w, d.width))
+        } else {
+            None
+        }
+    }
+
+    /** Find a door leading somewhere that overlaps the given rectangle.
+     */
+    pub fn door_at(&self, rect: Rectangle) -> Option<usize> {
+        (0..self.doors.len()).find(|&i| {
+            self.doors[i].target.is_some()
+                && self
+                    .door_rect(i)
+                    .map_or(false, |r| r.check_collision_recs(&rect))
+        })
+    }
+
+    /** Get the area covered by the wall pieces of each segment, doorways included.
+     */
+    pub fn wall_rects(&self) -> Vec<Rectangle> {
+        self.walls
+            .iter()
+            .filter_map(|seg| {
+                let (x, y) = seg.start();
+                if seg.is_horizontal() {
+                    let (_, h) = piece_dim(WALL_OBJ_ID);
+                    Some(rrect(x, y, seg.len(), h))
+                } else if seg.is_vertical() {
+                    let (w, _) = piece_dim(SIDE_WALL_OBJ_ID);
+                    Some(rrect(x, y, w, seg.len()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /** Check whether the placement flags of an object let it be where it is. Wall mounted objects have to
+     * hang on a wall, clear of the doorways, and floor objects have to stand inside the room, clear of
+     * the walls. Anything resting on a surface is allowed, as given by `on_surface`.
+     */
+    pub fn allows_placement(&self, obj: &GenericObject, on_surface: bool) -> bool {
+        let flags = obj.object_data.1.placement;
+        let walls = self.wall_rects();
+        let sprite = obj.get_obj_rect();
+        let feet = obj.get_collision_rect();
+
+        let in_doorway = (0..self.doors.len())
+            .filter_map(|d| self.door_rect(d))
+            .any(|d| d.check_collision_recs(&sprite));
+        let on_wall = walls.iter().any(|w| w.check_collision_recs(&sprite)) && !in_doorway;
+        let on_floor = feet.x >= 0.
+            && feet.y >= 0.
+            && feet.x + feet.width <= self.width() as f32
+            && feet.y + feet.height <= self.height() as f32
+            && !walls.iter().any(|w| w.check_collision_recs(&feet));
+
+        on_surface || (flags.wall_mounted && on_wall) || (flags.floor && on_floor)
+    }
+
+    /** Find the door that leads to the room with the given name.
+     */
+    pub fn door_to(&self, room: &str) -> Option<usize> {
+        self.doors
+            .iter()
+            .position(|d| d.target.as_deref() == Some(room))
+    }
+
+    /** Get where an object should stand to have just walked in through a door, given its bounding box
+     * relative to its position. The object is placed next to the door on the inside of the room, centred
+     * on the opening and aligned to Naomi's `STEP_SIZE`, so it isn't standing in the door.
+     */
+    pub fn entrance_for(&self, door: usize, b_box: Rectangle) -> Option<Position> {
+        let d = self.door_rect(door)?;
+        let step = STEP_SIZE as f32;
+        let align_down = |v: f32| (v / step).floor() as i32 * STEP_SIZE;
+        let align_up = |v: f32| (v / step).ceil() as i32 * STEP_SIZE;
+        let align = |v: f32| (v / step).round() as i32 * STEP_SIZE;
+
+        let (x, y) = if self.walls[self.doors[door].wall].is_horizontal() {
+            let x = align(d.x + d.width / 2. - (b_box.x + b_box.width / 2.));
+            if d.y + d.height / 2. < self.height() as f32 / 2. {
+                (x, align_up(d.y + d.height - b_box.y))
+            } else {
+                (x, align_down(d.y - b_box.y - b_box.height))
+            }
+        } else {
+            let y = align(d.y + d.height / 2. - (b_box.y + b_box.height / 2.));
+            if d.x + d.width / 2. < self.width() as f32 / 2. {
+                (align_up(d.x + d.width - b_box.x), y)
+            } else {
+                (align_down(d.x - b_box.x - b_box.width), y)
+            }
+        };
+        Some(Position::new(
+            x.clamp(0, (self.width() - 1).max(0)),
+            y.clamp(0, (self.height() - 1).max(0)),
+        ))
+    }
+
+    /** Create the wall objects for every segment in the room, leaving gaps for doors. Each piece gets
+     * its own ID taken from `next_id`. Segments that are neither horizontal nor vertical are skipped.
+     */
+    pub fn generate_walls(&self, next_id: &mut i32) -> Vec<GenericObject> {
+        let mut result = Vec::new();
+        for (i, seg) in self.walls.iter().enumerate() {
+            let (obj_type, horizontal) = if seg.is_horizontal() {
+                (WALL_OBJ_ID, true)
+            } else if seg.is_vertical() {
+                (SIDE_WALL_OBJ_ID, false)
+            } else {
+                continue;
+            };
+            let (x, y) = seg.start();
+
+            let mut along = 0;
+            while along < seg.len() {
+                let pos = if horizontal {
+                    Position::new(x + along, y)
+                } else {
+                    Position::new(x, y + along)
+                };
+                let piece = GenericObject::new(*next_id, obj_type, Some(pos));
+                let step = if horizontal {
+                    piece.width()
+                } else {
+                    piece.height()
+                }
+                .max(1);
+
+                if !self.is_door(i, along, step) {
+                    *next_id += 1;
+                    result.push(piece);
+                }
+                along += step;
+            }
+        }
+        result
+    }
+
+    /** Build the floor image by drawing each floor cell's tile from the catalogue, stretched to the
+     * size of a cell. Needs to be rebuilt whenever a cell is painted.
+     */
+    pub fn gen_floor_image(&self, catalogue: &TileCatalogue) -> Result<Image, String> {
+        let mut bckg = Image::gen_image_color(self.width(), self.height(), Color::WHITE);
+        let mut images: HashMap<&str, Image> = HashMap::new();
+        let (cols, rows) = self.floor_cells();
+        let size = self.tile_size.max(1);
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let (path, tint) = match catalogue.tiles.get(self.tile_at(col, row)) {
+                    Some(t) => (
+                        t.image.as_str(),
+                        Color::new(t.tint.0, t.tint.1, t.tint.2, 255),
+                    ),
+                    None => (self.floor_tile.as_str(), Color::WHITE),
+                };
+                if !images.contains_key(path) {
+                    images.insert(path, Image::load_image(path)?);
+                }
+                let tile = &images[path];
+                bckg.draw(
+                    tile,
+                    rrect(0, 0, tile.width(), tile.height()),
+                    rrect(col * size, row * size, size, size),
+                    tint,
+                );
+            }
+        }
+        Ok(bckg)
+    }
+}
+
+/** Get the size of a wall piece from its loaded object type.
+ */
+fn piece_dim(obj_type: i32) -> (i32, i32) {
+    LOADED_TEXTURES
+        .lock()
+        .expect("Unable to lock LOADED_TEXTURES mutex!")
+        .get(&obj_type)
+        .map_or((32, 64), |d| d.1.dim)
+}
+
+/* SAVE IMPLEMENTATIONS */
+impl Saveable<Self> for WallSegment {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = self.from.0.to_bytes();
+        result.extend(self.from.1.to_bytes());
+        result.extend(self.to.0.to_bytes());
+        result.extend(self.to.1.to_bytes());
+        result.extend(self.wallpaper.to_bytes());
+        result
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn Error>> {
+        read_slice(bytes, 16)?;
+        let wallpaper = Option::<usize>::from_bytes(&bytes[16..])?;
+        let result = WallSegment {
+            from: (
+                i32::from_bytes(&bytes[0..4])?.0,
+                i32::from_bytes(&bytes[4..8])?.0,
+            ),
+            to: (
+                i32::from_bytes(&bytes[8..12])?.0,
+                i32::from_bytes(&bytes[12..16])?.0,
+            ),
+            wallpaper: wallpaper.0,
+        };
+        Ok(SaveInfo(result, 16 + wallpaper.1))
+    }
+}
+
+impl Saveable<Self> for DoorConfig {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = self.wall.to_bytes();
+        result.extend(self.offset.to_bytes());
+        result.extend(self.width.to_bytes());
+        result.extend(self.target.to_bytes());
+        result
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn Error>> {
+        let wall = usize::from_bytes(bytes)?;
+        let offset = i32::from_bytes(&bytes[wall.1..])?;
+        let width = i32::from_bytes(&bytes[wall.1 + offset.1..])?;
+        let target = Option::<String>::from_bytes(&bytes[wall.1 + offset.1 + width.1..])?;
+        Ok(SaveInfo(
+            DoorConfig {
+                wall: wall.0,
+                offset: offset.0,
+                width: width.0,
+                target: target.0,
+            },
+            wall.1 + offset.1 + width.1 + target.1,
+        ))
+    }
+}
+
+impl Saveable<Self> for Room {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = self.name.to_bytes();
+        result.extend(self.size.0.to_bytes());
+        result.extend(self.size.1.to_bytes());
+        result.extend(self.floor_tile.to_bytes());
+        result.extend(self.tile_size.to_bytes());
+        result.extend(self.floor.to_bytes());
+        result.extend(self.map_pos.0.to_bytes());
+        result.extend(self.map_pos.1.to_bytes());
+        result.extend(self.walls.to_bytes());
+        result.extend(self.doors.to_bytes());
+        result
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn Error>> {
+        let mut bytes_read = 0;
+        let name = {
+            let base = String::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let width = {
+            let base = i32::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let height = {
+            let base = i32::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let floor_tile = {
+            let base = String::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let tile_size = {
+            let base = i32::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let floor = {
+            let base = Vec::<usize>::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let map_x = {
+            let base = i32::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let map_y = {
+            let base = i32::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let walls = {
+            let base = Vec::<WallSegment>::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+        let doors = {
+            let base = Vec::<DoorConfig>::from_bytes(&bytes[bytes_read..])?;
+            bytes_read += base.1;
+            base.0
+        };
+
+        Ok(SaveInfo(
+            Room {
+                name,
+                size: (width, height),
+                floor_tile,
+                tile_size,
+                floor,
+                map_pos: (map_x, map_y),
+                walls,
+                doors,
+            },
+            bytes_read,
+        ))
+    }
+}
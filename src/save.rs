@@ -11,6 +11,44 @@ use std::rc::Rc;
 use raylib::color;
 use raylib::prelude::*;
 
+/// Bytes every save file starts with. Files from before saves had a header start with the length of the
+/// object list instead, which can never match.
+pub const SAVE_MAGIC: [u8; 4] = *b"NIDS";
+
+/// Version of the save format written by this build. Bump it whenever anything that is saved changes.
+pub const SAVE_VERSION: i32 = 2;
+
+/// Version given to files without a header: a list of objects followed by Naomi, in the short object
+/// records from before objects could be flipped, rotated, scaled, stacked or switched between states.
+pub const LEGACY_SAVE_VERSION: i32 = 1;
+
+/// Get the header that starts a save file written by this build.
+pub fn write_header() -> Vec<u8> {
+    let mut result = SAVE_MAGIC.to_vec();
+    result.extend(SAVE_VERSION.to_bytes());
+    result
+}
+
+/// Read the header of a save file and get the version the file was written with. Files without a header
+/// are `LEGACY_SAVE_VERSION` and have nothing to skip. Files from a newer build can't be read.
+pub fn read_header(bytes: &[u8]) -> Result<SaveInfo<i32>, Box<dyn error::Error>> {
+    if !bytes.starts_with(&SAVE_MAGIC) {
+        return Ok(SaveInfo(LEGACY_SAVE_VERSION, 0));
+    }
+    let version = i32::from_bytes(&bytes[SAVE_MAGIC.len()..])?;
+    if version.0 > SAVE_VERSION {
+        return Err(format!("save file is from a newer version ({})", version.0).into());
+    }
+    Ok(SaveInfo(version.0, SAVE_MAGIC.len() + version.1))
+}
+
+/// Get the first `len` bytes, or an error if the data ends before then.
+pub fn read_slice(bytes: &[u8], len: usize) -> Result<&[u8], Box<dyn error::Error>> {
+    bytes
+        .get(..len)
+        .ok_or_else(|| "unexpected end of save data".into())
+}
+
 /// Holds a read object T, and the amount of bytes read for that object.
 /// Because Saveable::from_bytes() doesn't consume the bytes read, this allows the user
 /// to tell where in their bytearray they are.
@@ -44,7 +82,10 @@ impl Saveable<Self> for f32 {
         self.to_be_bytes().to_vec()
     }
     fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn error::Error>> {
-        Ok(SaveInfo(f32::from_be_bytes(bytes[0..4].try_into()?), 4))
+        Ok(SaveInfo(
+            f32::from_be_bytes(read_slice(bytes, 4)?.try_into()?),
+            4,
+        ))
     }
 }
 
@@ -53,7 +94,7 @@ impl Saveable<Self> for u8 {
         vec![*self]
     }
     fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn error::Error>> {
-        Ok(SaveInfo(bytes[0], 1))
+        Ok(SaveInfo(read_slice(bytes, 1)?[0], 1))
     }
 }
 
@@ -62,7 +103,7 @@ impl Saveable<Self> for i8 {
         vec![*self as u8]
     }
     fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn error::Error>> {
-        Ok(SaveInfo(bytes[0] as i8, 1))
+        Ok(SaveInfo(read_slice(bytes, 1)?[0] as i8, 1))
     }
 }
 
@@ -71,7 +112,10 @@ impl Saveable<Self> for i16 {
         self.to_be_bytes().to_vec()
     }
     fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn error::Error>> {
-        Ok(SaveInfo(i16::from_be_bytes(bytes[0..2].try_into()?), 2))
+        Ok(SaveInfo(
+            i16::from_be_bytes(read_slice(bytes, 2)?.try_into()?),
+            2,
+        ))
     }
 }
 
@@ -80,7 +124,10 @@ impl Saveable<Self> for i32 {
         self.to_be_bytes().to_vec()
     }
     fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn error::Error>> {
-        Ok(SaveInfo(i32::from_be_bytes(bytes[0..4].try_into()?), 4))
+        Ok(SaveInfo(
+            i32::from_be_bytes(read_slice(bytes, 4)?.try_into()?),
+            4,
+        ))
     }
 }
 
@@ -89,7 +136,10 @@ impl Saveable<Self> for usize {
         self.to_be_bytes().to_vec()
     }
     fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn error::Error>> {
-        Ok(SaveInfo(usize::from_be_bytes(bytes[0..8].try_into()?), 8))
+        Ok(SaveInfo(
+            usize::from_be_bytes(read_slice(bytes, 8)?.try_into()?),
+            8,
+        ))
     }
 }
 
@@ -111,9 +161,10 @@ impl Saveable<Self> for String {
     }
     fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<Self>, Box<dyn error::Error>> {
         let size = usize::from_bytes(bytes)?.0;
+        let end = size.checked_add(8).ok_or("bad string length")?;
         Ok(SaveInfo(
-            String::from_utf8(bytes[8..size + 8].to_vec())?,
-            8 + size,
+            String::from_utf8(read_slice(bytes, end)?[8..].to_vec())?,
+            end,
         ))
     }
 }
@@ -126,9 +177,10 @@ impl Saveable<String> for str {
     }
     fn from_bytes(bytes: &[u8]) -> Result<SaveInfo<String>, Box<dyn error::Error>> {
         let size = usize::from_bytes(bytes)?.0;
+        let end = size.checked_add(8).ok_or("bad string length")?;
         Ok(SaveInfo(
-            String::from_utf8(bytes[8..size + 8].to_vec())?,
-            8 + size,
+            String::from_utf8(read_slice(bytes, end)?[8..].to_vec())?,
+            end,
         ))
     }
 }
@@ -0,0 +1,271 @@
+//! # Selection
+//! `selection` holds the group of objects picked with shift-click or a rubber band, and the operations
+//! that can be done to all of them at once. Every operation is recorded in a `History`.
+
+use crate::game::*;
+use crate::history::*;
+use crate::object::*;
+use crate::spatial::*;
+use crate::util::rect_union;
+use raylib::prelude::*;
+use std::rc::Rc;
+
+/// Edge or centre line that selected objects can be lined up on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+    Top,
+    Bottom,
+    CentreX,
+    CentreY,
+}
+
+/// Direction objects are spread out along.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/** Objects that are selected, in the order they were picked.
+ */
+#[derive(Default)]
+pub struct Selection {
+    pub objects: Vec<GenObj>,
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Self {
+            objects: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn contains(&self, obj: &GenObj) -> bool {
+        self.objects.iter().any(|o| Rc::ptr_eq(o, obj))
+    }
+
+    /** Add an object unless it is already selected.
+     */
+    pub fn add(&mut self, obj: &GenObj) {
+        if !self.contains(obj) {
+            self.objects.push(obj.clone());
+        }
+    }
+
+    pub fn remove(&mut self, obj: &GenObj) {
+        self.objects.retain(|o| !Rc::ptr_eq(o, obj));
+    }
+
+    /** Select an object if it isn't, deselect it if it is.
+     */
+    pub fn toggle(&mut self, obj: &GenObj) {
+        if self.contains(obj) {
+            self.remove(obj);
+        } else {
+            self.add(obj);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.objects.clear();
+    }
+
+    /** Select only `obj`.
+     */
+    pub fn select_only(&mut self, obj: &GenObj) {
+        self.objects.clear();
+        self.objects.push(obj.clone());
+    }
+
+    /** Select every draggable object whose sprite overlaps `rect`. The objects are added to the selection
+     * when `additive` is true, otherwise they replace it.
+     */
+    pub fn select_rect(&mut self, objects: &[GenObj], rect: Rectangle, additive: bool) {
+        if !additive {
+            self.objects.clear();
+        }
+        for obj in objects.iter() {
+            let hit = {
+                let o = obj.borrow();
+                o.object_data.1.placement.draggable && o.get_obj_rect().check_collision_recs(&rect)
+            };
+            if hit {
+                self.add(obj);
+            }
+        }
+    }
+
+    /** Forget selected objects that are no longer in `objects`, after an undo or a change of room.
+     */
+    pub fn retain_existing(&mut self, objects: &[GenObj]) {
+        self.objects
+            .retain(|o| objects.iter().any(|other| Rc::ptr_eq(o, other)));
+    }
+
+    /** Selected objects that can be moved on their own. Objects sitting on another object follow it instead.
+     */
+    pub fn movable(&self) -> Vec<GenObj> {
+        self.objects
+            .iter()
+            .filter(|o| o.borrow().parent.is_none())
+            .cloned()
+            .collect()
+    }
+
+    /** Rectangle around the sprites of every selected object.
+     */
+    pub fn bounds(&self) -> Option<Rectangle> {
+        bounds_of(&self.objects)
+    }
+
+    /** Take every selected object out of the room, along with anything stacked on them. The removed
+     * objects are returned.
+     */
+    pub fn delete(
+        &mut self,
+        objects: &mut Vec<GenObj>,
+        grid: &mut SpatialGrid,
+        history: &mut History,
+    ) -> Vec<GenObj> {
+        let mut removed = std::mem::take(&mut self.objects);
+        // Stacked objects go in the same batch as what they sit on, so undo brings back the whole stack
+        let mut i = 0;
+        while i < removed.len() {
+            let id = removed[i].borrow().get_id();
+            for obj in objects.iter() {
+                if obj.borrow().parent == Some(id) && !removed.iter().any(|r| Rc::ptr_eq(r, obj)) {
+                    removed.push(obj.clone());
+                }
+            }
+            i += 1;
+        }
+        history.remove_all(&removed);
+        for obj in removed.iter() {
+            grid.remove(obj);
+        }
+        objects.retain(|o| !removed.iter().any(|r| Rc::ptr_eq(o, r)));
+        removed
+    }
+
+    /** Tint every selected object.
+     */
+    pub fn recolour(&self, color: Color, history: &mut History) {
+        history.modify_all(&self.objects, |o| o.colormod = color);
+    }
+
+    /** Push every selected object forwards or backwards in the drawing order.
+     */
+    pub fn change_depth(&self, delta: i32, history: &mut History) {
+        history.modify_all(&self.objects, |o| o.depthmod += delta);
+    }
+
+    /** Line the selected objects up on the edge or centre of the area they cover together.
+     */
+    pub fn align(&self, align: Align, history: &mut History) {
+        let objs = self.movable();
+        let bounds = match bounds_of(&objs) {
+            Some(b) => b,
+            None => return,
+        };
+        history.modify_all(&objs, |o| {
+            let rect = o.get_obj_rect();
+            let (dx, dy) = match align {
+                Align::Left => (bounds.x - rect.x, 0.),
+                Align::Right => (bounds.x + bounds.width - rect.x - rect.width, 0.),
+                Align::Top => (0., bounds.y - rect.y),
+                Align::Bottom => (0., bounds.y + bounds.height - rect.y - rect.height),
+                Align::CentreX => (bounds.x + (bounds.width - rect.width) / 2. - rect.x, 0.),
+                Align::CentreY => (0., bounds.y + (bounds.height - rect.height) / 2. - rect.y),
+            };
+            o.pos.x += dx.round() as i32;
+            o.pos.y += dy.round() as i32;
+        });
+    }
+
+    /** Spread the selected objects out so that the gaps between them are the same. The first and last
+     * objects along `axis` stay where they are. Needs at least three objects.
+     */
+    pub fn distribute(&self, axis: Axis, history: &mut History) {
+        let mut objs = self.movable();
+        if objs.len() < 3 {
+            return;
+        }
+        // Start and length of a sprite along the axis
+        let span = |o: &GenObj| {
+            let r = o.borrow().get_obj_rect();
+            match axis {
+                Axis::Horizontal => (r.x, r.width),
+                Axis::Vertical => (r.y, r.height),
+            }
+        };
+        objs.sort_by(|a, b| span(a).0.partial_cmp(&span(b).0).unwrap());
+
+        let first = span(&objs[0]);
+        let last = span(&objs[objs.len() - 1]);
+        let total: f32 = objs.iter().map(|o| span(o).1).sum();
+        let gap = (last.0 + last.1 - first.0 - total) / (objs.len() - 1) as f32;
+
+        // Work out how far each object has to move before moving any of them
+        let mut shifts = Vec::new();
+        let mut at = first.0;
+        for obj in objs.iter() {
+            let (start, len) = span(obj);
+            shifts.push((at - start).round() as i32);
+            at += len + gap;
+        }
+
+        let mut shifts = shifts.into_iter();
+        history.modify_all(&objs, |o| {
+            let shift = shifts.next().unwrap_or(0);
+            match axis {
+                Axis::Horizontal => o.pos.x += shift,
+                Axis::Vertical => o.pos.y += shift,
+            }
+        });
+    }
+
+    /** Draw an outline around every selected object.
+     */
+    pub fn draw_outlines(&self, rd: &mut RaylibTextureMode<RaylibDrawHandle>) {
+        let color = mutex_get(&BORDER_COLOR_FOCUSED);
+        for obj in self.objects.iter() {
+            rd.draw_rectangle_lines_ex(obj.borrow().get_obj_rect(), 1, color);
+        }
+    }
+}
+
+/// Rectangle around the sprites of some objects.
+fn bounds_of(objs: &[GenObj]) -> Option<Rectangle> {
+    let rects: Vec<Rectangle> = objs.iter().map(|o| o.borrow().get_obj_rect()).collect();
+    rect_union(&rects)
+}
+
+/** Rectangle with corners at two points, whichever way round they are.
+ */
+pub fn rect_between(a: Vector2, b: Vector2) -> Rectangle {
+    rrect(
+        a.x.min(b.x),
+        a.y.min(b.y),
+        (a.x - b.x).abs(),
+        (a.y - b.y).abs(),
+    )
+}
+
+/** Draw the rubber band being dragged out from `from` to `to`.
+ */
+pub fn draw_band(rd: &mut RaylibTextureMode<RaylibDrawHandle>, from: Vector2, to: Vector2) {
+    let color = mutex_get(&BORDER_COLOR_FOCUSED);
+    let rect = rect_between(from, to);
+    rd.draw_rectangle_rec(rect, color.fade(0.25));
+    rd.draw_rectangle_lines_ex(rect, 1, color);
+}
@@ -0,0 +1,190 @@
+//! # Snap
+//! `snap` decides where objects end up when they are set down, either by Naomi or with the mouse. Objects
+//! snap to a grid the player can change or turn off, and to guides lined up with the edges and centres of
+//! the objects around them.
+
+use crate::game::*;
+use crate::object::*;
+use crate::spatial::*;
+use raylib::prelude::*;
+use std::rc::Rc;
+
+/// Grid sizes the player can pick from, in pixels. 0 turns the grid off.
+pub const GRID_SIZES: [i32; 5] = [0, 4, 8, 16, 32];
+
+/// Grid size used when nothing else was picked. Matches the 4 pixels dragged objects have always snapped to.
+pub const DEFAULT_GRID_SIZE: i32 = 4;
+
+/// How close in pixels an edge or centre has to be to another object's to snap to it.
+pub const GUIDE_DISTANCE: f32 = 4.0;
+
+/** Guides found for an object being moved. `dx` and `dy` are how far the object has to move to line up
+ * with them, and `lines` are the guides to draw.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Guides {
+    pub dx: Option<f32>,
+    pub dy: Option<f32>,
+    pub lines: Vec<(Vector2, Vector2)>,
+}
+
+/** Snapping settings shared by everything that moves objects around.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snap {
+    pub grid: i32,
+    pub show_grid: bool,
+    pub guides: bool,
+}
+
+impl Default for Snap {
+    fn default() -> Self {
+        Self {
+            grid: DEFAULT_GRID_SIZE,
+            show_grid: false,
+            guides: true,
+        }
+    }
+}
+
+impl Snap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Switch to the next grid size, going back to off after the largest.
+     */
+    pub fn cycle_grid(&mut self) {
+        let i = GRID_SIZES.iter().position(|&g| g == self.grid).unwrap_or(0);
+        self.grid = GRID_SIZES[(i + 1) % GRID_SIZES.len()];
+    }
+
+    /** Name of the grid size for showing to the player.
+     */
+    pub fn grid_name(&self) -> String {
+        if self.grid > 1 {
+            format!("{} px", self.grid)
+        } else {
+            "off".to_string()
+        }
+    }
+
+    /** Round a coordinate to the nearest grid line.
+     */
+    pub fn snap_value(&self, v: i32) -> i32 {
+        if self.grid > 1 {
+            (v + self.grid / 2).div_euclid(self.grid) * self.grid
+        } else {
+            v
+        }
+    }
+
+    /** Find guides lining `rect` up with the collision rects of nearby objects. Objects in `ignore`, like the
+     * ones being moved, aren't lined up with.
+     */
+    pub fn find_guides(&self, rect: Rectangle, grid: &SpatialGrid, ignore: &[GenObj]) -> Guides {
+        let mut guides = Guides::default();
+        if !self.guides {
+            return guides;
+        }
+
+        let reach = rrect(
+            rect.x - rect.width,
+            rect.y - rect.height,
+            rect.width * 3.,
+            rect.height * 3.,
+        );
+        let others: Vec<Rectangle> = grid
+            .query_rect(reach)
+            .into_iter()
+            .filter(|o| !ignore.iter().any(|i| Rc::ptr_eq(i, o)))
+            .map(|o| o.borrow().get_collision_rect())
+            .collect();
+
+        // Closest pair of lines along one axis, as (distance to move, line of the other rect, other rect)
+        let closest = |mine: [f32; 3], theirs: &dyn Fn(&Rectangle) -> [f32; 3]| {
+            let mut best: Option<(f32, f32, Rectangle)> = None;
+            for other in others.iter() {
+                for &line in theirs(other).iter() {
+                    for &m in mine.iter() {
+                        let d = line - m;
+                        if d.abs() <= GUIDE_DISTANCE && best.map_or(true, |b| d.abs() < b.0.abs()) {
+                            best = Some((d, line, *other));
+                        }
+                    }
+                }
+            }
+            best
+        };
+
+        let xs = |r: &Rectangle| [r.x, r.x + r.width / 2., r.x + r.width];
+        let ys = |r: &Rectangle| [r.y, r.y + r.height / 2., r.y + r.height];
+
+        if let Some((d, x, other)) = closest(xs(&rect), &xs) {
+            guides.dx = Some(d);
+            let top = rect.y.min(other.y);
+            let bottom = (rect.y + rect.height).max(other.y + other.height);
+            guides.lines.push((rvec2(x, top), rvec2(x, bottom)));
+        }
+        if let Some((d, y, other)) = closest(ys(&rect), &ys) {
+            guides.dy = Some(d);
+            let left = rect.x.min(other.x);
+            let right = (rect.x + rect.width).max(other.x + other.width);
+            guides.lines.push((rvec2(left, y), rvec2(right, y)));
+        }
+        guides
+    }
+
+    /** Work out how far an object has to move to snap. Each axis lines up with a guide if there is one,
+     * otherwise with the grid. Objects in `ignore` aren't lined up with.
+     */
+    pub fn snap_offset(&self, obj: &GenObj, grid: &SpatialGrid, ignore: &[GenObj]) -> (i32, i32) {
+        let (pos, rect) = {
+            let o = obj.borrow();
+            (o.pos, o.get_collision_rect())
+        };
+        let guides = self.find_guides(rect, grid, ignore);
+        let dx = match guides.dx {
+            Some(d) => d.round() as i32,
+            None => self.snap_value(pos.x) - pos.x,
+        };
+        let dy = match guides.dy {
+            Some(d) => d.round() as i32,
+            None => self.snap_value(pos.y) - pos.y,
+        };
+        (dx, dy)
+    }
+
+    /** Move an object to where it snaps to.
+     */
+    pub fn snap_object(&self, obj: &GenObj, grid: &SpatialGrid, ignore: &[GenObj]) {
+        let (dx, dy) = self.snap_offset(obj, grid, ignore);
+        let mut o = obj.borrow_mut();
+        o.pos.x += dx;
+        o.pos.y += dy;
+    }
+
+    /** Draw the grid over a `width`×`height` room, if it is turned on.
+     */
+    pub fn draw_grid(&self, rd: &mut RaylibTextureMode<RaylibDrawHandle>, width: i32, height: i32) {
+        if !self.show_grid || self.grid <= 1 {
+            return;
+        }
+        let color = mutex_get(&BORDER_COLOR_NORMAL).fade(0.35);
+        for x in (0..=width).step_by(self.grid as usize) {
+            rd.draw_line(x, 0, x, height, color);
+        }
+        for y in (0..=height).step_by(self.grid as usize) {
+            rd.draw_line(0, y, width, y, color);
+        }
+    }
+
+    /** Draw the guides an object is lining up with.
+     */
+    pub fn draw_guides(&self, rd: &mut RaylibTextureMode<RaylibDrawHandle>, guides: &Guides) {
+        let color = mutex_get(&BORDER_COLOR_FOCUSED);
+        for (from, to) in guides.lines.iter() {
+            rd.draw_line_v(*from, *to, color);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! # Spatial
+//! `spatial` buckets objects on a uniform grid so that collision and picking queries only have to look
+//! at the objects near the area being asked about, instead of every object in the room.
+
+use crate::object::*;
+use raylib::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Width and height in pixels of a grid cell when no other size is asked for.
+pub const DEFAULT_CELL_SIZE: i32 = 32;
+
+/// Key used to tell objects apart. Object IDs aren't guaranteed to be unique, the allocation is.
+fn obj_key(obj: &GenObj) -> usize {
+    Rc::as_ptr(obj) as usize
+}
+
+/// The area an object is bucketed by. Covers both the collision box and the sprite, so that the same
+/// grid can answer collision queries and mouse picking.
+fn obj_area(obj: &GenericObject) -> Rectangle {
+    let col = obj.get_collision_rect();
+    let spr = obj.get_obj_rect();
+    let x = col.x.min(spr.x);
+    let y = col.y.min(spr.y);
+    rrect(
+        x,
+        y,
+        (col.x + col.width).max(spr.x + spr.width) - x,
+        (col.y + col.height).max(spr.y + spr.height) - y,
+    )
+}
+
+fn same_rect(a: Rectangle, b: Rectangle) -> bool {
+    a.x == b.x && a.y == b.y && a.width == b.width && a.height == b.height
+}
+
+/** Uniform grid of buckets holding the objects that overlap each cell. Objects move by having their
+ * position changed directly, so call `update()` or `update_all()` after moving them.
+ */
+pub struct SpatialGrid {
+    cell_size: i32,
+    cells: HashMap<(i32, i32), Vec<GenObj>>,
+    entries: HashMap<usize, Rectangle>,
+}
+
+impl SpatialGrid {
+    /** Create an empty grid with square cells of the given size in pixels.
+     */
+    pub fn new(cell_size: i32) -> Self {
+        Self {
+            cell_size: cell_size.max(1),
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    /** Create a grid holding all of the given objects.
+     */
+    pub fn from_objects(cell_size: i32, objects: &[GenObj]) -> Self {
+        let mut grid = Self::new(cell_size);
+        for obj in objects.iter() {
+            grid.insert(obj);
+        }
+        grid
+    }
+
+    /** Get the inclusive range of cells (min x, min y, max x, max y) that a rectangle touches.
+     */
+    fn cell_range(&self, rect: Rectangle) -> (i32, i32, i32, i32) {
+        let size = self.cell_size as f32;
+        (
+            (rect.x / size).floor() as i32,
+            (rect.y / size).floor() as i32,
+            ((rect.x + rect.width) / size).floor() as i32,
+            ((rect.y + rect.height) / size).floor() as i32,
+        )
+    }
+
+    fn add_to_cells(&mut self, obj: &GenObj, area: Rectangle) {
+        let (x0, y0, x1, y1) = self.cell_range(area);
+        for cx in x0..=x1 {
+            for cy in y0..=y1 {
+                self.cells
+                    .entry((cx, cy))
+                    .or_insert_with(Vec::new)
+                    .push(obj.clone());
+            }
+        }
+    }
+
+    fn remove_from_cells(&mut self, obj: &GenObj, area: Rectangle) {
+        let (x0, y0, x1, y1) = self.cell_range(area);
+        for cx in x0..=x1 {
+            for cy in y0..=y1 {
+                if let Some(cell) = self.cells.get_mut(&(cx, cy)) {
+                    cell.retain(|o| !Rc::ptr_eq(o, obj));
+                    if cell.is_empty() {
+                        self.cells.remove(&(cx, cy));
+                    }
+                }
+            }
+        }
+    }
+
+    /** Add an object to the grid. Adding an object that is already in the grid just updates it.
+     */
+    pub fn insert(&mut self, obj: &GenObj) {
+        if self.entries.contains_key(&obj_key(obj)) {
+            self.update(obj);
+            return;
+        }
+        let area = obj_area(&obj.borrow());
+        self.add_to_cells(obj, area);
+        self.entries.insert(obj_key(obj), area);
+    }
+
+    /** Take an object out of the grid.
+     */
+    pub fn remove(&mut self, obj: &GenObj) {
+        if let Some(area) = self.entries.remove(&obj_key(obj)) {
+            self.remove_from_cells(obj, area);
+        }
+    }
+
+    /** Move an object to the right cells if it has moved or changed size since it was last bucketed.
+     * Returns true if the object had to be moved.
+     */
+    pub fn update(&mut self, obj: &GenObj) -> bool {
+        let area = obj_area(&obj.borrow());
+        match self.entries.get(&obj_key(obj)).copied() {
+            Some(old) if same_rect(old, area) => false,
+            Some(old) => {
+                self.remove_from_cells(obj, old);
+                self.add_to_cells(obj, area);
+                self.entries.insert(obj_key(obj), area);
+                true
+            }
+            None => {
+                self.insert(obj);
+                true
+            }
+        }
+    }
+
+    /** Check whether an object is in the grid.
+     */
+    pub fn contains(&self, obj: &GenObj) -> bool {
+        self.entries.contains_key(&obj_key(obj))
+    }
+
+    /** Update every object in the slice. Cheap for objects that haven't moved.
+     */
+    pub fn update_all(&mut self, objects: &[GenObj]) {
+        for obj in objects.iter() {
+            self.update(obj);
+        }
+    }
+
+    /** Forget every object.
+     */
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+    }
+
+    /** Get the amount of objects in the grid.
+     */
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /** Get every object whose collision rectangle overlaps `rect`. Each object is returned once.
+     */
+    pub fn query_rect(&self, rect: Rectangle) -> Vec<GenObj> {
+        let mut seen = HashSet::new();
+        let mut result = Vec::new();
+        let (x0, y0, x1, y1) = self.cell_range(rect);
+        for cx in x0..=x1 {
+            for cy in y0..=y1 {
+                if let Some(cell) = self.cells.get(&(cx, cy)) {
+                    for obj in cell.iter() {
+                        if seen.insert(obj_key(obj))
+                            && obj
+                                .borrow()
+                                .get_collision_rect()
+                                .check_collision_recs(&rect)
+                        {
+                            result.push(obj.clone());
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /** Find the topmost object with a surface under the footprint of `obj`, which is where `obj` would
+     * be resting if it was put down now. Only stackable objects can rest on surfaces, and an object
+     * never rests on itself or on something sitting on it.
+     */
+    pub fn surface_under(&self, obj: &GenObj) -> Option<GenObj> {
+        let (id, footprint) = {
+            let o = obj.borrow();
+            if !o.object_data.1.placement.stackable {
+                return None;
+            }
+            (o.get_id(), o.get_footprint())
+        };
+        let size = self.cell_size as f32;
+        let cell = (
+            (footprint.x / size).floor() as i32,
+            (footprint.y / size).floor() as i32,
+        );
+        self.cells
+            .get(&cell)?
+            .iter()
+            .filter(|o| !Rc::ptr_eq(o, obj))
+            .filter(|o| {
+                let o = o.borrow();
+                o.parent != Some(id) && o.surface_contains(footprint)
+            })
+            .max_by_key(|o| o.borrow().get_draw_order())
+            .cloned()
+    }
+
+    /** Check whether `obj` overlaps something it can't share space with, like two desks. Objects in
+     * `ignore` are left out, and so are objects sitting on a surface.
+     */
+    pub fn is_blocked(&self, obj: &GenObj, ignore: &[GenObj]) -> bool {
+        let (rect, layer) = {
+            let o = obj.borrow();
+            (o.get_collision_rect(), o.get_layer())
+        };
+        self.query_rect(rect)
+            .iter()
+            .filter(|o| !Rc::ptr_eq(o, obj) && !ignore.iter().any(|i| Rc::ptr_eq(i, o)))
+            .any(|o| {
+                let o = o.borrow();
+                o.parent.is_none() && layer.collides_with(o.get_layer()) && o.collide(Some(&rect))
+            })
+    }
+
+    /** Get every object whose collision rectangle or sprite covers `point`. Use
+     * `Object::contains_point()` on the result for an exact test.
+     */
+    pub fn query_point(&self, point: Vector2) -> Vec<GenObj> {
+        let size = self.cell_size as f32;
+        let cell = (
+            (point.x / size).floor() as i32,
+            (point.y / size).floor() as i32,
+        );
+        match self.cells.get(&cell) {
+            Some(objs) => objs
+                .iter()
+                .filter(|o| {
+                    let o = o.borrow();
+                    o.get_collision_rect().check_collision_point_rec(point)
+                        || o.get_obj_rect().check_collision_point_rec(point)
+                })
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
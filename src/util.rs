@@ -3,12 +3,13 @@
 // Not for memory allocation or system-level things.
 
 use crate::game::*;
+use crate::palette::*;
 use raylib::consts::KeyboardKey::*;
 use raylib::ffi::Rectangle as ffirect;
 use raylib::prelude::*;
 use std::collections::HashMap;
 use std::ffi::CString;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 /// Get Vector of all unique categories that contain objects.
 pub fn get_all_types(only_placeable: bool) -> Vec<String> {
@@ -21,9 +22,7 @@ pub fn get_all_types(only_placeable: bool) -> Vec<String> {
     {
         let item = item.1; // get Value from (K, V) pair
         let item = &item.1; // get &ObjectConfig from Arc<(Texture2D, ObjectConfig>
-        if only_placeable && !(item.category.eq("sys") || item.category.eq("player")) {
-            result.push(item.category.clone());
-        }else if !only_placeable {
+        if !only_placeable || item.placement.player_placeable {
             result.push(item.category.clone());
         }
     }
@@ -48,6 +47,16 @@ pub fn get_all_objects() -> Vec<Arc<(Texture2D, ObjectConfig)>> {
     result
 }
 
+/// Get the ID of the object type with the given name.
+pub fn find_object_id(name: &str) -> Option<i32> {
+    LOADED_TEXTURES
+        .lock()
+        .expect("Unable to lock LOADED_TEXTURES mutex")
+        .values()
+        .find(|item| item.1.name == name)
+        .map(|item| item.1.id)
+}
+
 /// Get Hashmap with all object types sorted by their categories.
 pub fn get_all_objects_sorted(only_placeable: bool) -> HashMap<String, Vec<Arc<(Texture2D, ObjectConfig)>>> {
     let mut result = HashMap::new();
@@ -59,115 +68,365 @@ pub fn get_all_objects_sorted(only_placeable: bool) -> HashMap<String, Vec<Arc<(
     for t in types.iter() {
         let mut objs = Vec::new();
         for obj in all_objs.iter() {
-            if obj.1.category.eq(t) {
+            if obj.1.category.eq(t) && (!only_placeable || obj.1.placement.player_placeable) {
                 objs.push(obj.clone());
             }
         }
+        objs.sort_by(|a, b| a.1.get_display_name().cmp(b.1.get_display_name()));
         result.insert(t.clone(), objs);
     }
 
     result
 }
 
-/** Creates a safe text-input box which respects backspaces, shifts, and most regular characters.
-   Must be given a keyboardKey reference because RaylibDrawHandle doesn't implement DerefMut
-*/
-pub fn advanced_input(
-    rld: &mut RaylibDrawHandle,
-    key: &Option<KeyboardKey>,
-    font: impl AsRef<raylib::ffi::Font>,
-    bounds: Rectangle,
-    text: &mut String,
-    title: &str,
-) {
-    static mut UPPERCASE: bool = false; // Used to store shift keys for next run of function
+/// Frames a key has to be held before a `TextInput` starts repeating it.
+const TEXT_REPEAT_DELAY: i32 = 30;
 
-    let spacing = 1.0;
-    let fontsize = 20.0;
-    let title = Some(CString::new(title).expect("Failed to convert to CString!"));
-    let title = title.as_deref(); // Get Option<&CStr> from Option<CString>
-    let t_bound = Rectangle {
-        x: bounds.x,
-        y: bounds.y + 8.0,
-        width: bounds.width,
-        height: bounds.height - 8.0,
-    };
-    rld.gui_group_box(t_bound, title);
-    // Draw pre-existing text
-    rld.draw_text_ex(
-        &font,
-        text.as_str(),
-        rvec2(bounds.x as i32, bounds.y as i32 + 32),
-        fontsize,
-        spacing,
-        Color::BLACK,
-    );
+/// Frames between repeats of a key held down in a `TextInput`.
+const TEXT_REPEAT_RATE: i32 = 3;
 
-    // Ensure that the cursor is in the vicinity of the box to be drawn into
-    if !bounds.check_collision_point_rec(rld.get_mouse_position()) {
-        return;
+/** Text box that keeps its cursor, selection and focus between frames. The text itself belongs to the
+ * caller and is handed in every frame, so the same box can edit whichever string is being shown.
+ *
+ * Clicking the box or tabbing to it focuses it. While focused it takes any character typed, moves the
+ * cursor with the left and right arrow keys, Home and End, selects with Shift or by dragging the mouse, and selects all, copies, cuts
+ * and pastes with Ctrl+A, Ctrl+C, Ctrl+X and Ctrl+V.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TextInput {
+    pub focused: bool,
+    pub numeric: bool,
+    pub max_len: Option<usize>,
+    cursor: usize,
+    anchor: Option<usize>,
+    scroll: usize,
+    selecting: bool,
+    held: Option<(KeyboardKey, i32)>,
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Only accept the digits 0 to 9.
+     */
+    pub fn numeric(mut self) -> Self {
+        self.numeric = true;
+        self
+    }
+
+    /** Accept at most `len` characters.
+     */
+    pub fn max_len(mut self, len: usize) -> Self {
+        self.max_len = Some(len);
+        self
+    }
+
+    pub fn focus(&mut self) {
+        self.focused = true;
+    }
+
+    pub fn blur(&mut self) {
+        self.focused = false;
+        self.anchor = None;
+        self.selecting = false;
+    }
+
+    /** Get the range of characters that is selected, if any.
+     */
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        match self.anchor {
+            Some(a) if a != self.cursor => Some((a.min(self.cursor), a.max(self.cursor))),
+            _ => None,
+        }
     }
 
-    // Small closure to ensure that shifts are properly applied
-    let mut push_uppercase = |c: char| {
-        if unsafe { UPPERCASE } {
-            text.push(c.to_ascii_uppercase());
-            unsafe {
-                UPPERCASE = false;
+    /** Draw the box with `title` above it and handle its input, editing `text` in place. Returns true if
+     * the text was changed.
+     */
+    pub fn draw(
+        &mut self,
+        rld: &mut RaylibDrawHandle,
+        font: impl AsRef<raylib::ffi::Font>,
+        bounds: Rectangle,
+        text: &mut String,
+        title: &str,
+    ) -> bool {
+        let spacing = 1.0;
+        let fontsize = 20.0;
+        let title = Some(CString::new(title).expect("Failed to convert to CString!"));
+        let title = title.as_deref(); // Get Option<&CStr> from Option<CString>
+        let t_bound = Rectangle {
+            x: bounds.x,
+            y: bounds.y + 8.0,
+            width: bounds.width,
+            height: bounds.height - 8.0,
+        };
+        rld.gui_group_box(t_bound, title);
+
+        let text_pos = rvec2(bounds.x + 4.0, bounds.y + 32.0);
+        let text_width = bounds.width - 8.0;
+        let width_of = |s: &str| measure_text_ex(&font, s, fontsize, spacing).x;
+        // Character boundary closest to `x`, looking at the text shown from `scroll` onwards
+        let index_at = |text: &str, scroll: usize, x: f32| {
+            let len = text.chars().count();
+            (scroll..len)
+                .find(|&i| {
+                    let before = width_of(char_slice(text, scroll, i));
+                    let after = width_of(char_slice(text, scroll, i + 1));
+                    text_pos.x + (before + after) / 2. > x
+                })
+                .unwrap_or(len)
+        };
+
+        // The text may have been changed by someone else since the last frame
+        let len = text.chars().count();
+        self.cursor = self.cursor.min(len);
+        self.anchor = self.anchor.map(|a| a.min(len));
+        self.scroll = self.scroll.min(self.cursor);
+
+        let shift = rld.is_key_down(KEY_LEFT_SHIFT) || rld.is_key_down(KEY_RIGHT_SHIFT);
+        let ctrl = rld.is_key_down(KEY_LEFT_CONTROL) || rld.is_key_down(KEY_RIGHT_CONTROL);
+
+        // Tabbing to the box focuses it and tabbing away blurs it
+        let (nav_focused, _) = focus_widget(bounds, true);
+        if nav_focused {
+            self.focused = true;
+        } else if self.focused && focus_moved() {
+            self.blur();
+        }
+
+        // Clicking focuses the box and places the cursor, dragging selects
+        let mouse = rld.get_mouse_position();
+        if rld.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON) {
+            if bounds.check_collision_point_rec(mouse) {
+                let at = index_at(text.as_str(), self.scroll, mouse.x);
+                if shift && self.focused {
+                    self.anchor.get_or_insert(self.cursor);
+                } else {
+                    self.anchor = Some(at);
+                }
+                self.cursor = at;
+                self.focused = true;
+                self.selecting = true;
+            } else {
+                self.blur();
+            }
+        } else if self.selecting {
+            if rld.is_mouse_button_down(MouseButton::MOUSE_LEFT_BUTTON) {
+                self.cursor = index_at(text.as_str(), self.scroll, mouse.x);
+            } else {
+                self.selecting = false;
             }
-        } else {
-            text.push(c);
         }
-    };
 
-    if let Some(k) = key {
-        match k {
-            KEY_A => push_uppercase('a'),
-            KEY_B => push_uppercase('b'),
-            KEY_C => push_uppercase('c'),
-            KEY_D => push_uppercase('d'),
-            KEY_E => push_uppercase('e'),
-            KEY_F => push_uppercase('f'),
-            KEY_G => push_uppercase('g'),
-            KEY_H => push_uppercase('h'),
-            KEY_I => push_uppercase('i'),
-            KEY_J => push_uppercase('j'),
-            KEY_K => push_uppercase('k'),
-            KEY_L => push_uppercase('l'),
-            KEY_M => push_uppercase('m'),
-            KEY_N => push_uppercase('n'),
-            KEY_O => push_uppercase('o'),
-            KEY_P => push_uppercase('p'),
-            KEY_Q => push_uppercase('q'),
-            KEY_R => push_uppercase('r'),
-            KEY_S => push_uppercase('s'),
-            KEY_T => push_uppercase('t'),
-            KEY_U => push_uppercase('u'),
-            KEY_V => push_uppercase('v'),
-            KEY_W => push_uppercase('w'),
-            KEY_X => push_uppercase('x'),
-            KEY_Y => push_uppercase('y'),
-            KEY_Z => push_uppercase('z'),
-
-            // KEY_SPACE => text.push(' '),
-            KEY_MINUS => {
-                if unsafe { UPPERCASE } {
-                    text.push('_');
-                    unsafe {
-                        UPPERCASE = false;
+        // The caret keys belong to the box while it has the focus
+        if self.focused {
+            focus_grab();
+            focus_take(FocusInput::Left);
+            focus_take(FocusInput::Right);
+        }
+
+        let mut changed = false;
+        if self.focused {
+            if ctrl && rld.is_key_pressed(KEY_A) {
+                self.anchor = Some(0);
+                self.cursor = len;
+            }
+            let cut = ctrl && rld.is_key_pressed(KEY_X);
+            if cut || (ctrl && rld.is_key_pressed(KEY_C)) {
+                if let Some((a, b)) = self.selection() {
+                    set_clipboard(char_slice(text.as_str(), a, b));
+                    if cut {
+                        changed |= self.delete_selection(text);
                     }
                 }
             }
+            if ctrl && rld.is_key_pressed(KEY_V) {
+                if let Ok(clip) = rld.get_clipboard_text() {
+                    changed |= self.insert(text, clip.as_str());
+                }
+            }
 
-            KEY_BACKSPACE => {
-                text.pop();
+            if self.repeats(rld, KEY_LEFT) {
+                let to = match self.selection() {
+                    Some((a, _)) if !shift => a,
+                    _ => self.cursor.saturating_sub(1),
+                };
+                self.move_to(to, shift);
+            }
+            if self.repeats(rld, KEY_RIGHT) {
+                let to = match self.selection() {
+                    Some((_, b)) if !shift => b,
+                    _ => (self.cursor + 1).min(text.chars().count()),
+                };
+                self.move_to(to, shift);
+            }
+            if rld.is_key_pressed(KEY_HOME) {
+                self.move_to(0, shift);
+            }
+            if rld.is_key_pressed(KEY_END) {
+                self.move_to(text.chars().count(), shift);
             }
-            KEY_LEFT_SHIFT => unsafe {
-                UPPERCASE = true;
-            },
 
-            _ => (),
-        };
+            if self.repeats(rld, KEY_BACKSPACE) {
+                if !self.delete_selection(text) && self.cursor > 0 {
+                    self.cursor -= 1;
+                    text.remove(byte_index(text.as_str(), self.cursor));
+                }
+                changed = true;
+            }
+            if self.repeats(rld, KEY_DELETE) {
+                if !self.delete_selection(text) && self.cursor < text.chars().count() {
+                    text.remove(byte_index(text.as_str(), self.cursor));
+                }
+                changed = true;
+            }
+
+            // Characters typed this frame, already shifted and in any language the keyboard has
+            loop {
+                let code = unsafe { raylib::ffi::GetCharPressed() };
+                if code <= 0 {
+                    break;
+                }
+                if let Some(c) = std::char::from_u32(code as u32) {
+                    changed |= self.insert(text, c.to_string().as_str());
+                }
+            }
+        }
+
+        // Scroll the text so the cursor stays in the box
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        }
+        while self.scroll < self.cursor
+            && width_of(char_slice(text.as_str(), self.scroll, self.cursor)) > text_width
+        {
+            self.scroll += 1;
+        }
+        let len = text.chars().count();
+        let mut end = self.scroll;
+        while end < len && width_of(char_slice(text.as_str(), self.scroll, end + 1)) <= text_width {
+            end += 1;
+        }
+        let x_of = |i: usize| text_pos.x + width_of(char_slice(text.as_str(), self.scroll, i));
+
+        if self.focused {
+            rld.draw_rectangle_lines_ex(t_bound, 1, mutex_get(&BORDER_COLOR_FOCUSED));
+        }
+        if let Some((a, b)) = self.selection() {
+            let (a, b) = (a.max(self.scroll), b.min(end));
+            if a < b {
+                rld.draw_rectangle_rec(
+                    rrect(x_of(a), text_pos.y, x_of(b) - x_of(a), fontsize),
+                    mutex_get(&BORDER_COLOR_FOCUSED).fade(0.4),
+                );
+            }
+        }
+        rld.draw_text_ex(
+            &font,
+            char_slice(text.as_str(), self.scroll, end),
+            text_pos,
+            fontsize,
+            spacing,
+            Color::BLACK,
+        );
+        // Blinking cursor
+        if self.focused && (rld.get_time() * 2.0) as i64 % 2 == 0 {
+            rld.draw_rectangle_rec(
+                rrect(x_of(self.cursor), text_pos.y, 1, fontsize),
+                Color::BLACK,
+            );
+        }
+
+        changed
+    }
+
+    /// Move the cursor, stretching the selection if `extend` is true and dropping it otherwise.
+    fn move_to(&mut self, to: usize, extend: bool) {
+        if extend {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = to;
+    }
+
+    /// Remove the selected text. Returns false if nothing was selected.
+    fn delete_selection(&mut self, text: &mut String) -> bool {
+        let selection = self.selection();
+        self.anchor = None;
+        match selection {
+            Some((a, b)) => {
+                let range = byte_index(text.as_str(), a)..byte_index(text.as_str(), b);
+                text.replace_range(range, "");
+                self.cursor = a;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Put `s` in place of the selection, leaving out characters the box doesn't accept or has no room for.
+    /// Returns true if the text changed.
+    fn insert(&mut self, text: &mut String, s: &str) -> bool {
+        let numeric = self.numeric;
+        let accepted: Vec<char> = s
+            .chars()
+            .filter(|c| !c.is_control() && (!numeric || c.is_ascii_digit()))
+            .collect();
+        if accepted.is_empty() {
+            return false;
+        }
+        let deleted = self.delete_selection(text);
+        let room = self
+            .max_len
+            .map_or(usize::MAX, |m| m.saturating_sub(text.chars().count()));
+        let accepted: String = accepted.into_iter().take(room).collect();
+        text.insert_str(byte_index(text.as_str(), self.cursor), accepted.as_str());
+        self.cursor += accepted.chars().count();
+        deleted || !accepted.is_empty()
+    }
+
+    /// Check whether a key was pressed this frame, or has been held long enough to repeat.
+    fn repeats(&mut self, rl: &RaylibHandle, key: KeyboardKey) -> bool {
+        if rl.is_key_pressed(key) {
+            self.held = Some((key, 0));
+            return true;
+        }
+        match self.held {
+            Some((k, frames)) if k == key => {
+                if rl.is_key_down(key) {
+                    let frames = frames + 1;
+                    self.held = Some((k, frames));
+                    frames > TEXT_REPEAT_DELAY && frames % TEXT_REPEAT_RATE == 0
+                } else {
+                    self.held = None;
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Byte offset of the character at `index`, or the end of the text.
+fn byte_index(text: &str, index: usize) -> usize {
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(b, _)| b)
+}
+
+/// Characters `from` up to `to` of the text.
+fn char_slice(text: &str, from: usize, to: usize) -> &str {
+    &text[byte_index(text, from)..byte_index(text, to)]
+}
+
+/// Put text on the system clipboard. Works while drawing, unlike `RaylibHandle::set_clipboard_text()`.
+fn set_clipboard(text: &str) {
+    if let Ok(text) = CString::new(text) {
+        unsafe { raylib::ffi::SetClipboardText(text.as_ptr()) }
     }
 }
 
@@ -283,11 +542,14 @@ pub fn ds_rounded_button(
     let base_color: Color;
     let border_color: Color;
 
-    let mut pressed = false;
+    let (focused, mut pressed) = focus_widget(rec, active);
 
     if !active {
         base_color = mutex_get(&BASE_COLOR_DISABLED);
         border_color = mutex_get(&BORDER_COLOR_DISABLED);
+    } else if focused {
+        base_color = mutex_get(&BASE_COLOR_FOCUSED);
+        border_color = mutex_get(&BORDER_COLOR_FOCUSED);
     } else if !rec.check_collision_point_rec(rd.get_mouse_position()) {
         base_color = mutex_get(&BASE_COLOR_NORMAL);
         border_color = mutex_get(&BORDER_COLOR_NORMAL);
@@ -304,6 +566,9 @@ pub fn ds_rounded_button(
 
     rd.draw_rectangle_rounded(rec, 0.4, 5, base_color);
     rd.draw_rectangle_rounded_lines(rec, 0.4, 5, border_w as i32, border_color);
+    if focused {
+        ds_focus_ring(rd, rec, 0.4);
+    }
 
     if let Some(s) = text {
         // let _ctext = CString::new(s);
@@ -338,6 +603,420 @@ pub fn rect_midpoint(rec: Rectangle) -> (i32, i32) {
     )
 }
 
+/// Get the smallest rectangle covering all of `rects`, or None if there are none.
+pub fn rect_union(rects: &[Rectangle]) -> Option<Rectangle> {
+    rects
+        .iter()
+        .fold(None, |acc: Option<Rectangle>, r| match acc {
+            None => Some(*r),
+            Some(a) => {
+                let x = a.x.min(r.x);
+                let y = a.y.min(r.y);
+                let right = (a.x + a.width).max(r.x + r.width);
+                let bottom = (a.y + a.height).max(r.y + r.height);
+                Some(rrect(x, y, right - x, bottom - y))
+            }
+        })
+}
+
+/// Shrink a rectangle by `amount` on every side.
+pub fn inset(rec: Rectangle, amount: f32) -> Rectangle {
+    rrect(
+        rec.x + amount,
+        rec.y + amount,
+        (rec.width - 2.0 * amount).max(0.0),
+        (rec.height - 2.0 * amount).max(0.0),
+    )
+}
+
+/// Where a rectangle smaller than its area is put inside it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Centre,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+/// Place a `width` by `height` rectangle inside `area` at `anchor`. It is cut down to fit if it is too big.
+pub fn anchor_rect(area: Rectangle, width: f32, height: f32, anchor: Anchor) -> Rectangle {
+    use Anchor::*;
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    let fx = match anchor {
+        TopLeft | Left | BottomLeft => 0.0,
+        Top | Centre | Bottom => 0.5,
+        TopRight | Right | BottomRight => 1.0,
+    };
+    let fy = match anchor {
+        TopLeft | Top | TopRight => 0.0,
+        Left | Centre | Right => 0.5,
+        BottomLeft | Bottom | BottomRight => 1.0,
+    };
+    rrect(
+        area.x + (area.width - width) * fx,
+        area.y + (area.height - height) * fy,
+        width,
+        height,
+    )
+}
+
+/// How much room a part of a `Layout` takes up in the direction the layout flows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Size {
+    /// Exactly this many pixels, or less if there isn't room.
+    Fixed(f32),
+    /// A share of what the fixed parts leave over, weighed against the other `Fill` parts.
+    Fill(f32),
+}
+
+/// Direction a `Layout` puts its parts in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+    /// Side by side, from left to right.
+    Row,
+    /// On top of each other, from top to bottom.
+    Column,
+}
+
+/** Splits an area into rectangles for the `ds_*` widgets, so that menus keep their shape whatever size
+ * the window is. An area is split into a row or a column of parts with fixed or filling sizes, and the
+ * parts can be split again with another layout.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Layout {
+    pub area: Rectangle,
+    pub flow: Flow,
+    pub spacing: f32,
+}
+
+impl Layout {
+    pub fn new(area: Rectangle, flow: Flow) -> Self {
+        Self {
+            area,
+            flow,
+            spacing: 0.0,
+        }
+    }
+
+    /** Lay parts out side by side.
+     */
+    pub fn row(area: Rectangle) -> Self {
+        Self::new(area, Flow::Row)
+    }
+
+    /** Lay parts out on top of each other.
+     */
+    pub fn column(area: Rectangle) -> Self {
+        Self::new(area, Flow::Column)
+    }
+
+    /** Leave `amount` pixels free around the edges of the area.
+     */
+    pub fn padding(mut self, amount: f32) -> Self {
+        self.area = inset(self.area, amount);
+        self
+    }
+
+    /** Leave `amount` pixels between parts.
+     */
+    pub fn spacing(mut self, amount: f32) -> Self {
+        self.spacing = amount;
+        self
+    }
+
+    /** Get one rectangle per size, in order. Fixed parts are shrunk evenly if the area is too small for
+     * them, and fill parts share whatever is left.
+     */
+    pub fn split(&self, sizes: &[Size]) -> Vec<Rectangle> {
+        let length = match self.flow {
+            Flow::Row => self.area.width,
+            Flow::Column => self.area.height,
+        };
+        let room = (length - self.spacing * sizes.len().saturating_sub(1) as f32).max(0.0);
+        let fixed: f32 = sizes
+            .iter()
+            .map(|s| match s {
+                Size::Fixed(px) => *px,
+                Size::Fill(_) => 0.0,
+            })
+            .sum();
+        let weights: f32 = sizes
+            .iter()
+            .map(|s| match s {
+                Size::Fixed(_) => 0.0,
+                Size::Fill(w) => *w,
+            })
+            .sum();
+        let squeeze = if fixed > room { room / fixed } else { 1.0 };
+        let free = (room - fixed).max(0.0);
+
+        let mut at = 0.0;
+        sizes
+            .iter()
+            .map(|s| {
+                let len = match s {
+                    Size::Fixed(px) => px * squeeze,
+                    Size::Fill(w) if weights > 0.0 => free * w / weights,
+                    Size::Fill(_) => 0.0,
+                };
+                let rect = match self.flow {
+                    Flow::Row => rrect(self.area.x + at, self.area.y, len, self.area.height),
+                    Flow::Column => rrect(self.area.x, self.area.y + at, self.area.width, len),
+                };
+                at += len + self.spacing;
+                rect
+            })
+            .collect()
+    }
+
+    /** Split the area into `count` parts of the same size.
+     */
+    pub fn split_even(&self, count: usize) -> Vec<Rectangle> {
+        self.split(&vec![Size::Fill(1.0); count])
+    }
+}
+
+/// Navigation input waiting to be handled by the focused widget or the focus manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusInput {
+    Next,
+    Prev,
+    Up,
+    Down,
+    Left,
+    Right,
+    Activate,
+}
+
+/** Keyboard and gamepad focus for the ds_* widgets. Widgets register themselves in the order they are
+ * drawn, and the focused one is remembered by its place in that order. Input is read at the start of the
+ * frame, focused widgets take what they use while they are drawn, and whatever is left over moves the
+ * focus at the end of the frame.
+ */
+#[derive(Debug, Default)]
+struct FocusState {
+    widgets: Vec<(Rectangle, bool)>,
+    focused: Option<usize>,
+    pending: Vec<FocusInput>,
+    moved: bool,
+}
+
+lazy_static::lazy_static! {
+    static ref FOCUS: Mutex<FocusState> = Mutex::new(FocusState::default());
+}
+
+fn focus_state() -> std::sync::MutexGuard<'static, FocusState> {
+    FOCUS.lock().expect("Unable to lock focus state")
+}
+
+/** Read this frame's navigation input. Call once a frame before drawing any widgets.
+ *
+ * Tab and Shift+Tab (or the shoulder buttons) go through the widgets in order, the arrow keys (or the
+ * D-pad) go to the closest widget in that direction, and Enter (or A) activates the focused widget.
+ * Clicking anywhere, or pressing B, hands the keyboard back to the game.
+ */
+pub fn focus_begin_frame(rl: &RaylibHandle) {
+    let pad = |button| rl.is_gamepad_available(0) && rl.is_gamepad_button_pressed(0, button);
+    let shift = rl.is_key_down(KEY_LEFT_SHIFT) || rl.is_key_down(KEY_RIGHT_SHIFT);
+
+    let mut pending = Vec::new();
+    if rl.is_key_pressed(KEY_TAB) {
+        pending.push(if shift {
+            FocusInput::Prev
+        } else {
+            FocusInput::Next
+        });
+    }
+    if pad(GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_1) {
+        pending.push(FocusInput::Next);
+    }
+    if pad(GamepadButton::GAMEPAD_BUTTON_LEFT_TRIGGER_1) {
+        pending.push(FocusInput::Prev);
+    }
+    let dirs = [
+        (
+            KEY_UP,
+            GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP,
+            FocusInput::Up,
+        ),
+        (
+            KEY_DOWN,
+            GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN,
+            FocusInput::Down,
+        ),
+        (
+            KEY_LEFT,
+            GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_LEFT,
+            FocusInput::Left,
+        ),
+        (
+            KEY_RIGHT,
+            GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_RIGHT,
+            FocusInput::Right,
+        ),
+    ];
+    for (key, button, input) in dirs.iter() {
+        if rl.is_key_pressed(*key) || pad(*button) {
+            pending.push(*input);
+        }
+    }
+    if rl.is_key_pressed(KEY_ENTER) || pad(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN) {
+        pending.push(FocusInput::Activate);
+    }
+
+    let mut state = focus_state();
+    if rl.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON)
+        || pad(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT)
+    {
+        state.focused = None;
+    }
+    state.widgets.clear();
+    state.pending = pending;
+    state.moved = false;
+}
+
+/** Move the focus with whatever navigation input no widget took. Call once a frame after drawing every
+ * widget. Focus is dropped if the focused widget wasn't drawn this frame.
+ */
+pub fn focus_end_frame() {
+    let mut state = focus_state();
+    let count = state.widgets.len();
+    if state.focused.map_or(false, |f| f >= count) {
+        state.focused = None;
+    }
+    let enabled: Vec<usize> = (0..count).filter(|&i| state.widgets[i].1).collect();
+    if enabled.is_empty() {
+        state.focused = None;
+        return;
+    }
+
+    let pending = std::mem::take(&mut state.pending);
+    for input in pending.into_iter() {
+        let from = match state.focused {
+            Some(f) => f,
+            None => {
+                // Any navigation starts at the first widget
+                if input != FocusInput::Activate {
+                    state.focused = Some(enabled[0]);
+                    state.moved = true;
+                }
+                continue;
+            }
+        };
+        let to = match input {
+            FocusInput::Next => enabled
+                .iter()
+                .find(|&&i| i > from)
+                .or_else(|| enabled.first())
+                .copied(),
+            FocusInput::Prev => enabled
+                .iter()
+                .rev()
+                .find(|&&i| i < from)
+                .or_else(|| enabled.last())
+                .copied(),
+            FocusInput::Activate => None,
+            dir => {
+                // Closest widget whose centre lies that way, favouring ones straight ahead
+                let (fx, fy) = rect_midpoint(state.widgets[from].0);
+                enabled
+                    .iter()
+                    .filter(|&&i| i != from)
+                    .filter_map(|&i| {
+                        let (x, y) = rect_midpoint(state.widgets[i].0);
+                        let (dx, dy) = ((x - fx) as f32, (y - fy) as f32);
+                        let (ahead, across) = match dir {
+                            FocusInput::Up => (-dy, dx),
+                            FocusInput::Down => (dy, dx),
+                            FocusInput::Left => (-dx, dy),
+                            _ => (dx, dy),
+                        };
+                        if ahead > 0. {
+                            Some((i, ahead + across.abs() * 2.))
+                        } else {
+                            None
+                        }
+                    })
+                    .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                    .map(|(i, _)| i)
+            }
+        };
+        if let Some(to) = to {
+            state.moved |= to != from;
+            state.focused = Some(to);
+        }
+    }
+}
+
+/** Register a widget covering `rec`. Disabled widgets keep their place in the order but can't be focused.
+ * Returns whether the widget has the focus, and whether it was activated this frame.
+ */
+pub fn focus_widget(rec: Rectangle, enabled: bool) -> (bool, bool) {
+    let mut state = focus_state();
+    let index = state.widgets.len();
+    state.widgets.push((rec, enabled));
+    if !enabled || state.focused != Some(index) {
+        return (false, false);
+    }
+    let activated = match state
+        .pending
+        .iter()
+        .position(|i| *i == FocusInput::Activate)
+    {
+        Some(p) => {
+            state.pending.remove(p);
+            true
+        }
+        None => false,
+    };
+    (true, activated)
+}
+
+/** Take a navigation input so it doesn't move the focus. Only the focused widget should take input.
+ * Returns false if there was no such input this frame.
+ */
+pub fn focus_take(input: FocusInput) -> bool {
+    let mut state = focus_state();
+    match state.pending.iter().position(|i| *i == input) {
+        Some(p) => {
+            state.pending.remove(p);
+            true
+        }
+        None => false,
+    }
+}
+
+/** Focus the widget registered last, for widgets that can be focused some other way, like by clicking.
+ */
+pub fn focus_grab() {
+    let mut state = focus_state();
+    state.focused = state.widgets.len().checked_sub(1);
+}
+
+/** Whether the focus was moved by navigation last frame.
+ */
+pub fn focus_moved() -> bool {
+    focus_state().moved
+}
+
+/** Drop the focus, handing the keyboard back to the game.
+ */
+pub fn focus_clear() {
+    focus_state().focused = None;
+}
+
+/// Draw the ring around a focused widget.
+pub fn ds_focus_ring(rd: &mut RaylibDrawHandle, rec: Rectangle, roundness: f32) {
+    let ring = inset(rec, -3.0);
+    rd.draw_rectangle_rounded_lines(ring, roundness, 5, 2, mutex_get(&BORDER_COLOR_FOCUSED));
+}
+
 /// A scroll selection box that automatically determines the right height to draw itself to display
 /// a given number of items. If the given number of items is invalid, it will display 1 item.
 pub fn ds_scroll_selection_auto(
@@ -389,17 +1068,24 @@ pub fn ds_scroll_selection_ex(
 
     let num_items = (rec.height - 12.0) as i32 / item_rect.height as i32;
 
-    let mut k_input = false;    
+    let (focused, activated) = focus_widget(rec, true);
+    if focused {
+        ds_focus_ring(rd, rec, 0.1);
+    }
 
-    // Keyboard input
-    if rd.is_key_pressed(KEY_DOWN) {
-        *top_item_index += 1;
-        k_input = true;
-    }else if rd.is_key_pressed(KEY_UP) {
-        *top_item_index -= 1;
-        k_input = true;
+    let mut k_input = false;
+
+    // Keyboard input, only while focused. Going past either end leaves the arrow for moving the focus.
+    if focused {
+        if *top_item_index < selections.len() as i32 - 1 && focus_take(FocusInput::Down) {
+            *top_item_index += 1;
+            k_input = true;
+        } else if *top_item_index > 0 && focus_take(FocusInput::Up) {
+            *top_item_index -= 1;
+            k_input = true;
+        }
     }
-    
+
     // Scroll Selection Logic
     if *top_item_index >= selections.len() as i32 {
         *top_item_index = selections.len() as i32 - 1; // num_items;
@@ -441,7 +1127,7 @@ pub fn ds_scroll_selection_ex(
     }
     
     // Check if we pressed enter.
-    if rd.is_key_pressed(KEY_ENTER) {
+    if activated {
         *selection = *top_item_index;
         return true;
     }
@@ -487,19 +1173,27 @@ pub fn ds_draw_slider_centered(
         d.draw_text(title, center.x as i32, center.x as i32, 16, Color::BLACK);
     }
 
-    *value = d.gui_slider_bar(
-        rrect(
-            center.x - width / 2.0,
-            center.y + height / 2.0,
-            width,
-            height,
-        ),
-        None,
-        None,
-        *value as f32,
-        min_val,
-        max_val,
-    ) as i32;
+    let rec = rrect(
+        center.x - width / 2.0,
+        center.y + height / 2.0,
+        width,
+        height,
+    );
+    let (focused, _) = focus_widget(rec, true);
+    if focused {
+        // Left and right step the value while the slider has the focus
+        if focus_take(FocusInput::Left) {
+            *value = (*value - 1).max(min_val as i32);
+        }
+        if focus_take(FocusInput::Right) {
+            *value = (*value + 1).min(max_val as i32);
+        }
+    }
+
+    *value = d.gui_slider_bar(rec, None, None, *value as f32, min_val, max_val) as i32;
+    if focused {
+        ds_focus_ring(d, rec, 0.);
+    }
 }
 
 /// Draw a toggle with the default style, rounded corners, and centered at the position given by
@@ -533,7 +1227,8 @@ pub fn ds_draw_toggle_rounded(
     let text_color;
 
     let rec = rrect(rec.x + 2., rec.y + 2., rec.width - 4., rec.height - 4.);
-    let active = rec.check_collision_point_rec(d.get_mouse_position());
+    let (focused, activated) = focus_widget(rec, true);
+    let active = focused || rec.check_collision_point_rec(d.get_mouse_position());
 
     if *val {
         border_color = mutex_get(&BORDER_COLOR_PRESSED);
@@ -549,6 +1244,9 @@ pub fn ds_draw_toggle_rounded(
 
     d.draw_rectangle_rounded_lines(rec, 0.2, 5, 2, border_color);
     d.draw_rectangle_rounded(rec, 0.2, 5, base_color);
+    if focused {
+        ds_focus_ring(d, rec, 0.2);
+    }
     if let Some(txt) = text {
         draw_text_centered(
             d,
@@ -567,7 +1265,640 @@ pub fn ds_draw_toggle_rounded(
         //                text_color);
     }
 
-    if active && d.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON) {
+    if activated
+        || (rec.check_collision_point_rec(d.get_mouse_position())
+            && d.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON))
+    {
         *val = !*val;
     }
 }
+
+/// Width and height in pixels of a thumbnail in the furniture catalogue.
+pub const CATALOGUE_THUMB_SIZE: f32 = 64.0;
+
+/// Name of the catalogue tab that shows every category.
+pub const CATALOGUE_ALL: &str = "All";
+
+/** Grid of object thumbnails to pick furniture from, filtered by category tabs and by a search box. The
+ * open tab, the search and the scroll are kept between frames, while the objects are handed in every frame
+ * like the text of a `TextInput`.
+ *
+ * Every thumbnail is the first frame of the object's sprite sheet. Hovering or focusing one shows its name
+ * in a tooltip and its price and description under the grid.
+ */
+#[derive(Debug, Clone)]
+pub struct Catalogue {
+    pub tabs: Vec<String>,
+    pub tab: usize,
+    pub search: String,
+    search_input: TextInput,
+    scroll: i32,
+}
+
+impl Catalogue {
+    /** Create a catalogue with a tab for every category, after one showing everything.
+     */
+    pub fn new(categories: &[String]) -> Self {
+        let mut tabs = vec![CATALOGUE_ALL.to_string()];
+        tabs.extend(categories.iter().cloned());
+        Self {
+            tabs,
+            tab: 0,
+            search: String::new(),
+            search_input: TextInput::new().max_len(32),
+            scroll: 0,
+        }
+    }
+
+    /** Name of the open tab.
+     */
+    pub fn tab_name(&self) -> &str {
+        self.tabs
+            .get(self.tab)
+            .map_or(CATALOGUE_ALL, |t| t.as_str())
+    }
+
+    /** Focus the search box so typing filters straight away. Call when the catalogue is opened.
+     */
+    pub fn open(&mut self) {
+        self.search_input.focus();
+    }
+
+    /** Whether the search box has the keyboard.
+     */
+    pub fn typing(&self) -> bool {
+        self.search_input.focused
+    }
+
+    /** Check whether an object belongs in the open tab and matches the search, by name or by tag.
+     */
+    pub fn matches(&self, conf: &ObjectConfig) -> bool {
+        let tab = self.tab_name();
+        if tab != CATALOGUE_ALL && tab != conf.category {
+            return false;
+        }
+        let search = self.search.trim().to_lowercase();
+        search.is_empty()
+            || conf.get_display_name().to_lowercase().contains(&search)
+            || conf.name.to_lowercase().contains(&search)
+            || conf.tags.iter().any(|t| t.to_lowercase().contains(&search))
+    }
+
+    /** Draw the catalogue inside `rec` and handle its input. `objects` are the object types sorted by
+     * category, as given by `get_all_objects_sorted()`. Returns the ID of the object type picked, if any.
+     */
+    pub fn draw(
+        &mut self,
+        rd: &mut RaylibDrawHandle,
+        font: &Font,
+        rec: Rectangle,
+        objects: &HashMap<String, Vec<Arc<(Texture2D, ObjectConfig)>>>,
+    ) -> Option<i32> {
+        ds_rounded_rectangle(rd, rec, 0.05, 4);
+        ds_rounded_rectangle_lines(rd, rec, 0.05, 4, mutex_get(&BORDER_WIDTH));
+        let parts = Layout::column(rec).padding(8.0).spacing(8.0).split(&[
+            Size::Fixed(28.0), // tabs
+            Size::Fixed(64.0), // search
+            Size::Fill(1.0),   // thumbnails
+            Size::Fixed(20.0), // description
+        ]);
+
+        let tab_rects = Layout::row(parts[0])
+            .spacing(4.0)
+            .split_even(self.tabs.len());
+        for (i, tab_rect) in tab_rects.iter().enumerate() {
+            let mut open = self.tab == i;
+            ds_draw_toggle_rounded(rd, font, Some(self.tabs[i].as_str()), *tab_rect, &mut open);
+            if open && self.tab != i {
+                self.tab = i;
+                self.scroll = 0;
+            }
+        }
+
+        if self
+            .search_input
+            .draw(rd, font, parts[1], &mut self.search, "Search")
+        {
+            self.scroll = 0;
+        }
+
+        // Categories in tab order, each already sorted by name
+        let entries: Vec<&Arc<(Texture2D, ObjectConfig)>> = self
+            .tabs
+            .iter()
+            .filter_map(|t| objects.get(t))
+            .flatten()
+            .filter(|o| self.matches(&o.1))
+            .collect();
+
+        let grid = parts[2];
+        let step = CATALOGUE_THUMB_SIZE + 8.0;
+        let cols = ((grid.width + 8.0) / step).floor().max(1.0) as usize;
+        let rows = ((grid.height + 8.0) / step).floor().max(1.0) as i32;
+        let total_rows = entries.len().div_ceil(cols) as i32;
+        let max_scroll = (total_rows - rows).max(0);
+
+        let mouse = rd.get_mouse_position();
+        if grid.check_collision_point_rec(mouse) {
+            self.scroll -= rd.get_mouse_wheel_move() as i32;
+        }
+        self.scroll = self.scroll.clamp(0, max_scroll);
+
+        if entries.is_empty() {
+            let (cx, cy) = rect_midpoint(grid);
+            draw_text_centered(rd, font, "Nothing matches", cx, cy, 16, Color::BLACK);
+        }
+
+        let mut picked = None;
+        let mut hovered: Option<(&Arc<(Texture2D, ObjectConfig)>, Rectangle)> = None;
+        let first = self.scroll as usize * cols;
+        let shown = entries.iter().skip(first).take(cols * rows as usize);
+        for (n, entry) in shown.enumerate() {
+            let (col, row) = ((n % cols) as f32, (n / cols) as i32);
+            let cell = rrect(
+                grid.x + col * step,
+                grid.y + row as f32 * step,
+                CATALOGUE_THUMB_SIZE,
+                CATALOGUE_THUMB_SIZE,
+            );
+            let (focused, activated) = focus_widget(cell, true);
+            let mouse_over = cell.check_collision_point_rec(mouse);
+
+            // Moving the focus past the top or bottom row scrolls instead
+            if focused {
+                if row == rows - 1 && self.scroll < max_scroll && focus_take(FocusInput::Down) {
+                    self.scroll += 1;
+                } else if row == 0 && self.scroll > 0 && focus_take(FocusInput::Up) {
+                    self.scroll -= 1;
+                }
+            }
+
+            let (base_color, border_color) = if focused || mouse_over {
+                (
+                    mutex_get(&BASE_COLOR_FOCUSED),
+                    mutex_get(&BORDER_COLOR_FOCUSED),
+                )
+            } else {
+                (
+                    mutex_get(&BASE_COLOR_NORMAL),
+                    mutex_get(&BORDER_COLOR_NORMAL),
+                )
+            };
+            rd.draw_rectangle_rounded(cell, 0.2, 5, base_color);
+            rd.draw_rectangle_rounded_lines(cell, 0.2, 5, 2, border_color);
+
+            // First frame of the sprite sheet, scaled to fit the cell
+            let (w, h) = (entry.1.dim.0 as f32, entry.1.dim.1 as f32);
+            if w > 0. && h > 0. {
+                let scale =
+                    ((CATALOGUE_THUMB_SIZE - 8.0) / w).min((CATALOGUE_THUMB_SIZE - 8.0) / h);
+                let thumb = anchor_rect(cell, w * scale, h * scale, Anchor::Centre);
+                rd.draw_texture_pro(
+                    &entry.0,
+                    rrect(0, 0, w, h),
+                    thumb,
+                    rvec2(0, 0),
+                    0.,
+                    Color::WHITE,
+                );
+            }
+            if focused {
+                ds_focus_ring(rd, cell, 0.2);
+            }
+
+            if mouse_over || (focused && hovered.is_none()) {
+                hovered = Some((*entry, cell));
+            }
+            if activated
+                || (mouse_over && rd.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON))
+            {
+                picked = Some(entry.1.id);
+            }
+        }
+
+        if let Some((entry, cell)) = hovered {
+            // Price and description under the grid
+            let mut info = entry.1.get_display_name().to_string();
+            if let Some(price) = entry.1.price {
+                info.push_str(format!(" - ${}", price).as_str());
+            }
+            if let Some(desc) = &entry.1.description {
+                info.push_str(format!(": {}", desc).as_str());
+            }
+            rd.draw_text_ex(
+                font,
+                info.as_str(),
+                rvec2(parts[3].x, parts[3].y),
+                16.,
+                1.,
+                Color::BLACK,
+            );
+
+            // Name in a tooltip under the thumbnail, kept inside the catalogue
+            let name = entry.1.get_display_name();
+            let size = measure_text_ex(font, name, 16., 1.);
+            let tip_w = size.x + 8.;
+            let tip_x = (cell.x + (cell.width - tip_w) / 2.)
+                .max(rec.x)
+                .min(rec.x + rec.width - tip_w);
+            let tip = rrect(tip_x, cell.y + cell.height + 2., tip_w, size.y + 4.);
+            rd.draw_rectangle_rounded(tip, 0.3, 4, mutex_get(&BASE_COLOR_NORMAL));
+            rd.draw_rectangle_rounded_lines(tip, 0.3, 4, 1, mutex_get(&BORDER_COLOR_NORMAL));
+            let (cx, cy) = rect_midpoint(tip);
+            draw_text_centered(rd, font, name, cx, cy, 16, Color::BLACK);
+        }
+
+        picked
+    }
+}
+
+/// Part of the colour picker being dragged with the mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PickerPart {
+    Square,
+    Hue,
+    Alpha,
+}
+
+/** Colour being picked in a `ds_color_picker`, kept as hue, saturation and value so that the hue isn't
+ * lost while the colour is grey. `palette` is the palette whose swatches are shown.
+ */
+#[derive(Debug, Clone)]
+pub struct ColorPicker {
+    pub hue: f32,
+    pub saturation: f32,
+    pub value: f32,
+    pub alpha: u8,
+    pub palette: usize,
+    swatch_scroll: i32,
+    dragging: Option<PickerPart>,
+}
+
+impl Default for ColorPicker {
+    fn default() -> Self {
+        Self {
+            hue: 0.,
+            saturation: 0.,
+            value: 1.,
+            alpha: 255,
+            palette: 0,
+            swatch_scroll: 0,
+            dragging: None,
+        }
+    }
+}
+
+impl ColorPicker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn color(&self) -> Color {
+        hsv_to_color(self.hue, self.saturation, self.value, self.alpha)
+    }
+
+    /** Start from an existing colour. The hue is kept if the colour is grey.
+     */
+    pub fn set_color(&mut self, color: Color) {
+        let (hue, saturation, value) = color_to_hsv(color);
+        if saturation > 0. && value > 0. {
+            self.hue = hue;
+        }
+        self.saturation = saturation;
+        self.value = value;
+        self.alpha = color.a;
+    }
+}
+
+/// Draw a checkerboard, to show through colours that aren't fully opaque.
+fn draw_checker(rd: &mut RaylibDrawHandle, rec: Rectangle) {
+    let size = 6.;
+    let mut y = 0.;
+    while y < rec.height {
+        let mut x = 0.;
+        while x < rec.width {
+            let light = ((x / size) as i32 + (y / size) as i32) % 2 == 0;
+            rd.draw_rectangle_rec(
+                rrect(
+                    rec.x + x,
+                    rec.y + y,
+                    size.min(rec.width - x),
+                    size.min(rec.height - y),
+                ),
+                if light {
+                    Color::WHITE
+                } else {
+                    Color::LIGHTGRAY
+                },
+            );
+            x += size;
+        }
+        y += size;
+    }
+}
+
+/// Draw a colour swatch, focusable like any other widget. Returns whether it has the focus, and whether
+/// it was clicked or activated.
+fn ds_swatch(rd: &mut RaylibDrawHandle, rec: Rectangle, color: Color) -> (bool, bool) {
+    let (focused, activated) = focus_widget(rec, true);
+    draw_checker(rd, rec);
+    rd.draw_rectangle_rec(rec, color);
+    rd.draw_rectangle_lines_ex(rec, 1, mutex_get(&BORDER_COLOR_NORMAL));
+    if focused {
+        ds_focus_ring(rd, rec, 0.);
+    }
+    let clicked = rec.check_collision_point_rec(rd.get_mouse_position())
+        && rd.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON);
+    (focused, activated || clicked)
+}
+
+/// A colour picker with a saturation and value square, hue and alpha bars, the recently used colours
+/// and the swatches of a palette. Swatches are added to the palette shown with "Add" and removed by
+/// right clicking them.
+/// Returns the colour picked, if any, and whether `palettes` was changed and should be saved.
+pub fn ds_color_picker(
+    rd: &mut RaylibDrawHandle,
+    font: &Font,
+    rec: Rectangle,
+    picker: &mut ColorPicker,
+    palettes: &mut Palettes,
+) -> (Option<Color>, bool) {
+    let mut picked = None;
+    let mut changed = false;
+    let swatch_size = 24.;
+
+    ds_rounded_rectangle(rd, rec, 0.05, 4);
+    ds_rounded_rectangle_lines(rd, rec, 0.05, 4, mutex_get(&BORDER_WIDTH));
+    let parts = Layout::column(rec).padding(8.0).spacing(6.0).split(&[
+        Size::Fill(1.0),                    // square, bars and preview
+        Size::Fixed(16.0),                  // "Recent"
+        Size::Fixed(swatch_size),           // recent colours
+        Size::Fixed(swatch_size),           // palette name and buttons
+        Size::Fixed(swatch_size * 2. + 4.), // palette swatches
+        Size::Fixed(28.0),                  // apply
+    ]);
+    let pick = Layout::row(parts[0]).spacing(8.0).split(&[
+        Size::Fill(1.0),
+        Size::Fixed(20.0),
+        Size::Fixed(20.0),
+        Size::Fixed(72.0),
+    ]);
+    let (square, hue_bar, alpha_bar) = (pick[0], pick[1], pick[2]);
+
+    /* Mouse Dragging */
+    let mouse = rd.get_mouse_position();
+    if rd.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON) {
+        picker.dragging = if square.check_collision_point_rec(mouse) {
+            Some(PickerPart::Square)
+        } else if hue_bar.check_collision_point_rec(mouse) {
+            Some(PickerPart::Hue)
+        } else if alpha_bar.check_collision_point_rec(mouse) {
+            Some(PickerPart::Alpha)
+        } else {
+            None
+        };
+    } else if !rd.is_mouse_button_down(MouseButton::MOUSE_LEFT_BUTTON) {
+        picker.dragging = None;
+    }
+    // How far along or down a rectangle a mouse coordinate is, from 0 to 1
+    let across = |rec: Rectangle, x: f32| ((x - rec.x) / rec.width.max(1.)).clamp(0., 1.);
+    let down = |rec: Rectangle, y: f32| ((y - rec.y) / rec.height.max(1.)).clamp(0., 1.);
+    match picker.dragging {
+        Some(PickerPart::Square) => {
+            picker.saturation = across(square, mouse.x);
+            picker.value = 1. - down(square, mouse.y);
+        }
+        Some(PickerPart::Hue) => picker.hue = down(hue_bar, mouse.y) * 359.,
+        Some(PickerPart::Alpha) => picker.alpha = ((1. - down(alpha_bar, mouse.y)) * 255.) as u8,
+        None => (),
+    }
+
+    /* Keyboard Nudging */
+    let (focused, _) = focus_widget(square, true);
+    if focused {
+        if focus_take(FocusInput::Left) {
+            picker.saturation = (picker.saturation - 0.05).max(0.);
+        }
+        if focus_take(FocusInput::Right) {
+            picker.saturation = (picker.saturation + 0.05).min(1.);
+        }
+        if focus_take(FocusInput::Up) {
+            picker.value = (picker.value + 0.05).min(1.);
+        }
+        if focus_take(FocusInput::Down) {
+            picker.value = (picker.value - 0.05).max(0.);
+        }
+    }
+    let (hue_focused, _) = focus_widget(hue_bar, true);
+    if hue_focused {
+        if focus_take(FocusInput::Up) {
+            picker.hue = (picker.hue - 5.).max(0.);
+        }
+        if focus_take(FocusInput::Down) {
+            picker.hue = (picker.hue + 5.).min(359.);
+        }
+    }
+    let (alpha_focused, _) = focus_widget(alpha_bar, true);
+    if alpha_focused {
+        if focus_take(FocusInput::Up) {
+            picker.alpha = picker.alpha.saturating_add(15);
+        }
+        if focus_take(FocusInput::Down) {
+            picker.alpha = picker.alpha.saturating_sub(15);
+        }
+    }
+
+    /* Saturation And Value Square */
+    let pure = hsv_to_color(picker.hue, 1., 1., 255);
+    let (sx, sy, sw, sh) = (
+        square.x as i32,
+        square.y as i32,
+        square.width as i32,
+        square.height as i32,
+    );
+    rd.draw_rectangle_gradient_h(sx, sy, sw, sh, Color::WHITE, pure);
+    rd.draw_rectangle_gradient_v(sx, sy, sw, sh, Color::BLACK.fade(0.), Color::BLACK);
+    let marker = rvec2(
+        square.x + picker.saturation * square.width,
+        square.y + (1. - picker.value) * square.height,
+    );
+    rd.draw_circle_lines(marker.x as i32, marker.y as i32, 5., Color::WHITE);
+    rd.draw_circle_lines(marker.x as i32, marker.y as i32, 6., Color::BLACK);
+    if focused {
+        ds_focus_ring(rd, square, 0.);
+    }
+
+    /* Hue Bar */
+    for i in 0..6 {
+        let y0 = hue_bar.y + hue_bar.height * i as f32 / 6.;
+        let y1 = hue_bar.y + hue_bar.height * (i + 1) as f32 / 6.;
+        rd.draw_rectangle_gradient_v(
+            hue_bar.x as i32,
+            y0 as i32,
+            hue_bar.width as i32,
+            y1 as i32 - y0 as i32,
+            hsv_to_color(i as f32 * 60., 1., 1., 255),
+            hsv_to_color((i + 1) as f32 * 60., 1., 1., 255),
+        );
+    }
+    let hue_y = hue_bar.y + picker.hue / 359. * hue_bar.height;
+    rd.draw_rectangle_lines_ex(
+        rrect(hue_bar.x - 2., hue_y - 2., hue_bar.width + 4., 4),
+        1,
+        Color::BLACK,
+    );
+    if hue_focused {
+        ds_focus_ring(rd, hue_bar, 0.);
+    }
+
+    /* Alpha Bar */
+    let opaque = hsv_to_color(picker.hue, picker.saturation, picker.value, 255);
+    draw_checker(rd, alpha_bar);
+    rd.draw_rectangle_gradient_v(
+        alpha_bar.x as i32,
+        alpha_bar.y as i32,
+        alpha_bar.width as i32,
+        alpha_bar.height as i32,
+        opaque,
+        opaque.fade(0.),
+    );
+    let alpha_y = alpha_bar.y + (1. - picker.alpha as f32 / 255.) * alpha_bar.height;
+    rd.draw_rectangle_lines_ex(
+        rrect(alpha_bar.x - 2., alpha_y - 2., alpha_bar.width + 4., 4),
+        1,
+        Color::BLACK,
+    );
+    if alpha_focused {
+        ds_focus_ring(rd, alpha_bar, 0.);
+    }
+
+    /* Preview */
+    let color = picker.color();
+    let preview = Layout::column(pick[3]).spacing(4.0).split(&[
+        Size::Fixed(48.0),
+        Size::Fixed(16.0),
+        Size::Fill(1.0),
+    ]);
+    draw_checker(rd, preview[0]);
+    rd.draw_rectangle_rec(preview[0], color);
+    rd.draw_rectangle_lines_ex(preview[0], 1, mutex_get(&BORDER_COLOR_NORMAL));
+    let hex = format!(
+        "#{:02X}{:02X}{:02X}{:02X}",
+        color.r, color.g, color.b, color.a
+    );
+    let (cx, cy) = rect_midpoint(preview[1]);
+    draw_text_centered(rd, font, hex.as_str(), cx, cy, 12, Color::BLACK);
+
+    /* Recent Colours */
+    rd.draw_text_ex(
+        font,
+        "Recent",
+        rvec2(parts[1].x, parts[1].y),
+        16.,
+        1.,
+        Color::BLACK,
+    );
+    let recent: Vec<Color> = palettes.recent.iter().map(|c| from_rgba(*c)).collect();
+    for (i, c) in recent.iter().enumerate() {
+        let x = parts[2].x + i as f32 * (swatch_size + 4.);
+        if x + swatch_size > parts[2].x + parts[2].width {
+            break;
+        }
+        if ds_swatch(rd, rrect(x, parts[2].y, swatch_size, swatch_size), *c).1 {
+            picker.set_color(*c);
+            picked = Some(*c);
+        }
+    }
+
+    /* Palette */
+    picker.palette = picker
+        .palette
+        .min(palettes.palettes.len().saturating_sub(1));
+    let bar = Layout::row(parts[3]).spacing(4.0).split(&[
+        Size::Fixed(swatch_size),
+        Size::Fill(1.0),
+        Size::Fixed(swatch_size),
+        Size::Fixed(56.0),
+        Size::Fixed(56.0),
+    ]);
+    let count = palettes.palettes.len().max(1);
+    if ds_rounded_button(rd, font, bar[0], Some("<"), count > 1).0 {
+        picker.palette = (picker.palette + count - 1) % count;
+        picker.swatch_scroll = 0;
+    }
+    if let Some(p) = palettes.palettes.get(picker.palette) {
+        let (cx, cy) = rect_midpoint(bar[1]);
+        draw_text_centered(rd, font, p.name.as_str(), cx, cy, 16, Color::BLACK);
+    }
+    if ds_rounded_button(rd, font, bar[2], Some(">"), count > 1).0 {
+        picker.palette = (picker.palette + 1) % count;
+        picker.swatch_scroll = 0;
+    }
+    if ds_rounded_button(rd, font, bar[3], Some("Add"), !palettes.palettes.is_empty()).0 {
+        palettes.add_color(picker.palette, color);
+        changed = true;
+    }
+    if ds_rounded_button(rd, font, bar[4], Some("New"), true).0 {
+        picker.palette = palettes.new_palette();
+        picker.swatch_scroll = 0;
+        changed = true;
+    }
+
+    let swatches = parts[4];
+    let cols = ((swatches.width + 4.) / (swatch_size + 4.)).floor().max(1.) as usize;
+    let rows = ((swatches.height + 4.) / (swatch_size + 4.))
+        .floor()
+        .max(1.) as usize;
+    let colors = palettes.colors(picker.palette);
+    // Palettes with more colours than fit scroll a row at a time
+    let max_scroll = (colors.len().div_ceil(cols) as i32 - rows as i32).max(0);
+    if swatches.check_collision_point_rec(mouse) {
+        picker.swatch_scroll -= rd.get_mouse_wheel_move() as i32;
+    }
+    picker.swatch_scroll = picker.swatch_scroll.clamp(0, max_scroll);
+    let first = picker.swatch_scroll as usize * cols;
+    let mut remove = None;
+    for (i, c) in colors.iter().enumerate().skip(first).take(cols * rows) {
+        let cell = rrect(
+            swatches.x + ((i - first) % cols) as f32 * (swatch_size + 4.),
+            swatches.y + ((i - first) / cols) as f32 * (swatch_size + 4.),
+            swatch_size,
+            swatch_size,
+        );
+        let (focused, clicked) = ds_swatch(rd, cell, *c);
+        if clicked {
+            picker.set_color(*c);
+            picked = Some(*c);
+        }
+        // Moving the focus past the top or bottom row scrolls instead
+        let row = (i - first) / cols;
+        if focused {
+            if row == rows - 1 && picker.swatch_scroll < max_scroll && focus_take(FocusInput::Down)
+            {
+                picker.swatch_scroll += 1;
+            } else if row == 0 && picker.swatch_scroll > 0 && focus_take(FocusInput::Up) {
+                picker.swatch_scroll -= 1;
+            }
+        }
+        if cell.check_collision_point_rec(mouse)
+            && rd.is_mouse_button_pressed(MouseButton::MOUSE_RIGHT_BUTTON)
+        {
+            remove = Some(i);
+        }
+    }
+    if let Some(i) = remove {
+        palettes.remove_color(picker.palette, i);
+        changed = true;
+    }
+
+    let apply = anchor_rect(parts[5], 160.0, 28.0, Anchor::Centre);
+    if ds_rounded_button(rd, font, apply, Some("Apply"), true).0 {
+        picked = Some(color);
+    }
+
+    if let Some(c) = picked {
+        palettes.use_color(c);
+        changed = true;
+    }
+    (picked, changed)
+}
@@ -0,0 +1,135 @@
+//! # Viewport
+//! `viewport` fits the game onto the window. The room is drawn onto a canvas of a fixed size, which is then
+//! scaled up by a whole number so that pixels stay square and sharp. Whatever is left of the window is
+//! filled with black bars. All mouse positions used in the room have to go through `screen_to_world`.
+
+use crate::camera::Camera;
+use raylib::core::window::{get_monitor_height, get_monitor_width};
+use raylib::prelude::*;
+
+/// Width in pixels of the canvas the room is drawn onto.
+pub const CANVAS_WIDTH: i32 = 320;
+
+/// Height in pixels of the canvas the room is drawn onto.
+pub const CANVAS_HEIGHT: i32 = 320;
+
+/** Canvas the room is drawn onto and where it ends up in the window. `scale` is how many window pixels a
+ * canvas pixel takes up, and `dest` is the part of the window the canvas covers.
+ */
+pub struct Viewport {
+    pub canvas: RenderTexture2D,
+    pub camera: Camera,
+    pub width: i32,
+    pub height: i32,
+    pub scale: i32,
+    pub dest: Rectangle,
+    windowed: (i32, i32),
+}
+
+impl Viewport {
+    /** Create a `width`×`height` canvas looking into a room covering `bounds`.
+     */
+    pub fn new(
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        width: i32,
+        height: i32,
+        bounds: Rectangle,
+    ) -> Self {
+        let canvas = rl
+            .load_render_texture(thread, width as u32, height as u32)
+            .expect("Unable to create canvas!");
+        let mut viewport = Self {
+            canvas,
+            camera: Camera::new(width, height, bounds),
+            width,
+            height,
+            scale: 1,
+            dest: rrect(0, 0, width, height),
+            windowed: (rl.get_screen_width(), rl.get_screen_height()),
+        };
+        viewport.update(rl);
+        viewport
+    }
+
+    /** Fit the canvas to the current size of the window. Call once a frame so that resizing is picked up.
+     */
+    pub fn update(&mut self, rl: &RaylibHandle) {
+        let (scr_w, scr_h) = (rl.get_screen_width(), rl.get_screen_height());
+        self.scale = (scr_w / self.width).min(scr_h / self.height).max(1);
+        let (w, h) = (self.width * self.scale, self.height * self.scale);
+        self.dest = rrect((scr_w - w) / 2, (scr_h - h) / 2, w, h);
+    }
+
+    /** Switch between a window and fullscreen. The window size is restored when leaving fullscreen.
+     */
+    pub fn toggle_fullscreen(&mut self, rl: &mut RaylibHandle) {
+        if rl.is_window_fullscreen() {
+            rl.toggle_fullscreen();
+            rl.set_window_size(self.windowed.0, self.windowed.1);
+        } else {
+            // Fullscreen keeps the window size as the resolution, so take the monitor's first
+            self.windowed = (rl.get_screen_width(), rl.get_screen_height());
+            rl.set_window_size(get_monitor_width(0), get_monitor_height(0));
+            rl.toggle_fullscreen();
+        }
+        self.update(rl);
+    }
+
+    /** Convert a position in the window to a position on the canvas.
+     */
+    pub fn screen_to_canvas(&self, pos: Vector2) -> Vector2 {
+        rvec2(
+            (pos.x - self.dest.x) / self.scale as f32,
+            (pos.y - self.dest.y) / self.scale as f32,
+        )
+    }
+
+    /** Convert a position on the canvas to a position in the window.
+     */
+    pub fn canvas_to_screen(&self, pos: Vector2) -> Vector2 {
+        rvec2(
+            pos.x * self.scale as f32 + self.dest.x,
+            pos.y * self.scale as f32 + self.dest.y,
+        )
+    }
+
+    /** Convert a position in the window to a position in the room.
+     */
+    pub fn screen_to_world(&self, pos: Vector2) -> Vector2 {
+        self.camera.screen_to_world(self.screen_to_canvas(pos))
+    }
+
+    /** Convert a position in the room to a position in the window.
+     */
+    pub fn world_to_screen(&self, pos: Vector2) -> Vector2 {
+        self.canvas_to_screen(self.camera.world_to_screen(pos))
+    }
+
+    /** Whether a position in the window is on the canvas rather than the black bars around it.
+     */
+    pub fn contains(&self, pos: Vector2) -> bool {
+        self.dest.check_collision_point_rec(pos)
+    }
+
+    /** Pass the mouse on to the camera. See `Camera::handle_input`.
+     */
+    pub fn handle_input(&mut self, rl: &RaylibHandle, zoom: bool) {
+        let mouse = self.screen_to_canvas(rl.get_mouse_position());
+        self.camera.handle_input(rl, mouse, zoom);
+    }
+
+    /** Draw the canvas scaled up into the window, with black bars around it.
+     */
+    pub fn draw(&self, rd: &mut RaylibDrawHandle) {
+        rd.clear_background(Color::BLACK);
+        rd.draw_texture_pro(
+            &self.canvas,
+            rrect(0, 0, self.width, -self.height),
+            self.dest,
+            rvec2(0, 0),
+            0.,
+            Color::WHITE,
+        );
+    }
+}